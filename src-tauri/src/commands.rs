@@ -2,36 +2,198 @@ use crate::{
     AppState,
     core::{
         api_client::{self, plugins::McPluginReport},
-        auth::Account,
+        auth::{
+            Account, AccountRefreshError,
+            microsoft::{DeviceCode, MicrosoftAuth},
+            upsert_account,
+            yggdrasil::{self, AuthlibInjectorServer, LoginResult, PendingLogin, YggdrasilClient},
+        },
+        downloader::TaskItemReport,
         game::GameInstance,
+        instance_settings::InstanceSettings,
         java::{JavaRuntime, JavaRuntimeVecExt},
-        launcher::LaunchOption,
+        launch_script::ScriptFormat,
+        launcher::{GameLaunchError, LaunchOption},
         mcmod,
+        process::{GameExit, GameLogEvent, RunningGameInfo},
         repository::GameRepository,
     },
     setup::ConfigManager,
 };
 use std::sync::Arc;
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, State, ipc::Channel};
 use tauri_plugin_dialog::DialogExt;
 use tokio::sync::Mutex;
 
-#[tauri::command]
-pub fn launch_game(_app: AppHandle, state: State<'_, Arc<Mutex<AppState>>>) -> Result<(), String> {
+/// launch the active game instance, files missing or corrupted are repaired first
+/// and the progress of repairing is sent through the channel like a download task.
+/// the output of the game is streamed through `on_log`, returns the pid of the game
+#[tauri::command(rename_all = "snake_case")]
+pub async fn launch_game(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    on_event: Channel<TaskItemReport>,
+    on_log: Channel<GameLogEvent>,
+    task_id: i32,
+) -> Result<u32, LaunchGameError> {
     log::info!("launch_game invoked from js.");
-    let guard = state.blocking_lock();
+    refresh_active_account(&state).await?;
+    let guard = state.lock().await;
     let launch_option = LaunchOption::from_state(&guard);
+    let game_registry = Arc::clone(&guard.game_registry);
+    let task_manager = Arc::clone(&guard.task_manager);
     drop(guard);
-    if let Err(e) = launch_option {
+    let launch_option = launch_option.map_err(|e| {
         log::error!("launch_game: {:?}", e);
-        return Err(e.to_string());
-    } else {
-        if let Err(e) = launch_option.unwrap().launch_checked() {
+        match e {
+            GameLaunchError::AccountExpired(_) => LaunchGameError::ReloginRequired(e.to_string()),
+            _ => LaunchGameError::Failed(e.to_string()),
+        }
+    })?;
+    // repairing the files can be paused and cancelled like a download task
+    let task = task_manager.register(task_id);
+    match launch_option
+        .launch_checked(on_event, task_id, &task.signal)
+        .await
+    {
+        Ok(child) => Ok(game_registry.supervise(
+            child,
+            launch_option.game_instance(),
+            launch_option.game_dir(),
+            on_log,
+        )),
+        Err(e) => {
             log::error!("launch_game: {:?}", e);
-            return Err(e.to_string());
+            Err(LaunchGameError::Failed(e.to_string()))
+        }
+    }
+}
+
+/// why `launch_game` failed, the frontend asks the user to log in again on `relogin_required`
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum LaunchGameError {
+    ReloginRequired(String),
+    Failed(String),
+}
+
+/// refresh the token of the active account if it is stale, the refreshed account is saved.
+/// failures other than a dead login are only logged while the old token may still work,
+/// and reported as they are once it has expired, as logging in again doesn't help
+async fn refresh_active_account(
+    state: &State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), LaunchGameError> {
+    let guard = state.lock().await;
+    let Some(account) = guard.active_account.clone() else {
+        return Ok(());
+    };
+    let config = guard.pcl_setup_info.microsoft_auth.clone();
+    drop(guard);
+    let refreshed = match account.refresh_if_needed(&config).await {
+        Ok(Some(refreshed)) => refreshed,
+        Ok(None) => return Ok(()),
+        Err(AccountRefreshError::ReloginRequired(message)) => {
+            log::warn!("login of {} expired: {}", account.username(), message);
+            return Err(LaunchGameError::ReloginRequired(format!(
+                "the login of {} has expired, log in again",
+                account.username()
+            )));
         }
+        Err(err) if !account.is_expired() => {
+            log::warn!("failed to refresh {}: {}", account.username(), err);
+            return Ok(());
+        }
+        Err(err) => {
+            log::error!("failed to refresh expired {}: {}", account.username(), err);
+            return Err(LaunchGameError::Failed(format!(
+                "the login of {} has expired and failed to refresh: {}",
+                account.username(),
+                err
+            )));
+        }
+    };
+    let mut guard = state.lock().await;
+    upsert_account(&mut guard.accounts, refreshed.clone());
+    guard.active_account = Some(Arc::new(refreshed));
+    drop(guard);
+    save_config().await.map_err(LaunchGameError::Failed)
+}
+
+/// the script that runs the active instance the same way as `launch_game`,
+/// written to `save_path` if given
+#[tauri::command(rename_all = "snake_case")]
+pub async fn export_launch_script(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    format: Option<ScriptFormat>,
+    mask_tokens: bool,
+    save_path: Option<std::path::PathBuf>,
+) -> Result<String, String> {
+    let launch_option =
+        LaunchOption::from_state(&*state.lock().await).map_err(|e| e.to_string())?;
+    let script = launch_option
+        .export_script(format.unwrap_or_else(ScriptFormat::current), mask_tokens)
+        .map_err(|e| e.to_string())?;
+    if let Some(save_path) = save_path {
+        std::fs::write(&save_path, &script).map_err(|e| e.to_string())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&save_path, std::fs::Permissions::from_mode(0o755))
+                .map_err(|e| e.to_string())?;
+        }
+        log::info!("launch script exported to {:?}", save_path);
     }
-    Ok(())
+    Ok(script)
+}
+
+#[tauri::command]
+pub async fn get_running_games(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<RunningGameInfo>, ()> {
+    Ok(state.lock().await.game_registry.running_games())
+}
+
+/// the exit record of a game launched before, including the last lines of output
+#[tauri::command]
+pub async fn get_game_exit(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    pid: u32,
+) -> Result<Option<GameExit>, ()> {
+    Ok(state.lock().await.game_registry.exit_record(pid))
+}
+
+#[tauri::command]
+pub async fn kill_game(state: State<'_, Arc<Mutex<AppState>>>, pid: u32) -> Result<(), String> {
+    let game_registry = Arc::clone(&state.lock().await.game_registry);
+    game_registry.kill(pid).map_err(|err| err.to_string())
+}
+
+/// pause a running download task, the files being downloaded keep their progress
+#[tauri::command(rename_all = "snake_case")]
+pub async fn pause_task(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    task_id: i32,
+) -> Result<(), String> {
+    let task_manager = Arc::clone(&state.lock().await.task_manager);
+    task_manager.pause(task_id).map_err(|err| err.to_string())
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn resume_task(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    task_id: i32,
+) -> Result<(), String> {
+    let task_manager = Arc::clone(&state.lock().await.task_manager);
+    task_manager.resume(task_id).map_err(|err| err.to_string())
+}
+
+/// cancel a running or paused download task, the partial files are removed
+#[tauri::command(rename_all = "snake_case")]
+pub async fn cancel_task(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    task_id: i32,
+) -> Result<(), String> {
+    let task_manager = Arc::clone(&state.lock().await.task_manager);
+    task_manager.cancel(task_id).map_err(|err| err.to_string())
 }
 
 #[tauri::command]
@@ -114,6 +276,199 @@ pub async fn get_account() -> Option<Account> {
     account.as_deref().cloned()
 }
 
+/// log in a microsoft account with the device code flow, the code is sent to `on_device_code`.
+/// the account logged in becomes the active one
+#[tauri::command(rename_all = "snake_case")]
+pub async fn microsoft_login(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    on_device_code: Channel<DeviceCode>,
+) -> Result<Account, String> {
+    let config = state.lock().await.pcl_setup_info.microsoft_auth.clone();
+    let account = MicrosoftAuth::new(config)
+        .login(|device_code| {
+            let _ = on_device_code.send(device_code.clone());
+        })
+        .await
+        .map_err(|err| {
+            log::error!("microsoft_login: {}", err);
+            err.to_string()
+        })?;
+    save_account(&state, account.clone()).await?;
+    Ok(account)
+}
+
+/// log in to a yggdrasil server with the password, `server_url` is the website or the api
+/// of an authlib-injector server, or none for mojang.
+/// the account logged in becomes the active one, unless a profile has to be selected first
+#[tauri::command(rename_all = "snake_case")]
+pub async fn yggdrasil_login(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    server_url: Option<String>,
+    username: String,
+    password: String,
+) -> Result<LoginResult, String> {
+    let server = match server_url {
+        Some(server_url) => Some(
+            AuthlibInjectorServer::discover(&server_url)
+                .await
+                .map_err(|err| err.to_string())?,
+        ),
+        None => None,
+    };
+    let result = yggdrasil::login(server, &username, &password)
+        .await
+        .map_err(|err| {
+            log::error!("yggdrasil_login: {}", err);
+            err.to_string()
+        })?;
+    if let LoginResult::LoggedIn { account } = &result {
+        save_account(&state, account.clone()).await?;
+    }
+    Ok(result)
+}
+
+/// finish a yggdrasil login which has several profiles with the one chosen
+#[tauri::command(rename_all = "snake_case")]
+pub async fn yggdrasil_select_profile(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    pending: PendingLogin,
+    profile_id: String,
+) -> Result<Account, String> {
+    let account = yggdrasil::select_profile(pending, &profile_id)
+        .await
+        .map_err(|err| err.to_string())?;
+    save_account(&state, account.clone()).await?;
+    Ok(account)
+}
+
+/// sign out all the sessions of a yggdrasil account, `server_url` is the same as `yggdrasil_login`
+#[tauri::command(rename_all = "snake_case")]
+pub async fn yggdrasil_signout(
+    server_url: Option<String>,
+    username: String,
+    password: String,
+) -> Result<(), String> {
+    let server = match server_url {
+        Some(server_url) => Some(
+            AuthlibInjectorServer::discover(&server_url)
+                .await
+                .map_err(|err| err.to_string())?,
+        ),
+        None => None,
+    };
+    YggdrasilClient::of_server(server.as_ref())
+        .signout(&username, &password)
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn get_accounts(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<Account>, ()> {
+    Ok(state.lock().await.accounts.clone())
+}
+
+/// add an account, such as an offline one, the same account added before is replaced
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_account(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    account: Account,
+) -> Result<(), String> {
+    save_account(&state, account).await
+}
+
+/// create an offline account, its uuid is derived from the username unless given
+#[tauri::command(rename_all = "snake_case")]
+pub async fn add_offline_account(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    username: String,
+    uuid: Option<String>,
+) -> Result<Account, String> {
+    let account = Account::offline(&username, uuid.as_deref()).map_err(|err| err.to_string())?;
+    save_account(&state, account.clone()).await?;
+    Ok(account)
+}
+
+/// remove the account, its token is invalidated on the server if possible.
+/// the first account left becomes the active one if the active one is removed
+#[tauri::command(rename_all = "snake_case")]
+pub async fn remove_account(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    account_index: usize,
+) -> Result<(), String> {
+    let mut guard = state.lock().await;
+    if account_index >= guard.accounts.len() {
+        return Err(format!("account {} not found", account_index));
+    }
+    let account = guard.accounts.remove(account_index);
+    if guard
+        .active_account
+        .as_ref()
+        .is_some_and(|active| active.is_same(&account))
+    {
+        guard.active_account = guard.accounts.first().cloned().map(Arc::new);
+    }
+    drop(guard);
+    if let Err(err) = account.invalidate().await {
+        log::warn!("failed to invalidate {}: {}", account.username(), err);
+    }
+    save_config().await
+}
+
+/// move the account at `from` to `to`, the accounts between are shifted
+#[tauri::command(rename_all = "snake_case")]
+pub async fn move_account(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    from: usize,
+    to: usize,
+) -> Result<(), String> {
+    let mut guard = state.lock().await;
+    let len = guard.accounts.len();
+    if from >= len || to >= len {
+        return Err(format!("account index out of range: {} -> {}", from, to));
+    }
+    let account = guard.accounts.remove(from);
+    guard.accounts.insert(to, account);
+    drop(guard);
+    save_config().await
+}
+
+#[tauri::command(rename_all = "snake_case")]
+pub async fn switch_account(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    account_index: usize,
+) -> Result<Account, String> {
+    let mut guard = state.lock().await;
+    let account = guard
+        .accounts
+        .get(account_index)
+        .cloned()
+        .ok_or_else(|| format!("account {} not found", account_index))?;
+    guard.active_account = Some(Arc::new(account.clone()));
+    drop(guard);
+    save_config().await?;
+    Ok(account)
+}
+
+/// add the account logged in and make it the active one
+async fn save_account(
+    state: &State<'_, Arc<Mutex<AppState>>>,
+    account: Account,
+) -> Result<(), String> {
+    let mut guard = state.lock().await;
+    upsert_account(&mut guard.accounts, account.clone());
+    guard.active_account = Some(Arc::new(account));
+    drop(guard);
+    save_config().await
+}
+
+/// the lock of the state must not be held, it is locked again to save
+async fn save_config() -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(|| ConfigManager::instance().save())
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| format!("{:?}", err))
+}
+
 #[tauri::command(rename_all = "snake_case")]
 pub async fn get_instances_in_repository(
     state: State<'_, Arc<Mutex<AppState>>>,
@@ -147,6 +502,67 @@ pub fn select_instance(
     guard.active_game_instance = Some(Arc::new(instance.clone()));
 }
 
+/// settings of the active instance
+#[tauri::command]
+pub async fn get_instance_settings(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<InstanceSettings, String> {
+    let guard = state.lock().await;
+    let instance = guard
+        .active_game_instance
+        .as_ref()
+        .ok_or("no instance selected")?;
+    InstanceSettings::load(&instance.directory).map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn set_instance_settings(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    settings: InstanceSettings,
+) -> Result<(), String> {
+    let guard = state.lock().await;
+    let instance = guard
+        .active_game_instance
+        .as_ref()
+        .ok_or("no instance selected")?;
+    log::info!("saving settings of instance {}", instance.id);
+    settings
+        .save(&instance.directory)
+        .map_err(|err| err.to_string())
+}
+
+/// the game directory of the active instance, decided by version isolation
+async fn active_game_dir(
+    state: &State<'_, Arc<Mutex<AppState>>>,
+) -> Result<std::path::PathBuf, String> {
+    let guard = state.lock().await;
+    let instance = guard
+        .active_game_instance
+        .as_ref()
+        .ok_or("no instance selected")?;
+    let settings = InstanceSettings::load(&instance.directory).map_err(|err| err.to_string())?;
+    Ok(instance.game_directory(
+        guard.pcl_setup_info.version_isolation,
+        settings.version_isolation,
+    ))
+}
+
+#[tauri::command]
+pub async fn get_instance_mods(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<std::path::PathBuf>, String> {
+    let game_dir = active_game_dir(&state).await?;
+    GameInstance::list_mods(&game_dir).map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn get_instance_saves(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<std::path::PathBuf>, String> {
+    let game_dir = active_game_dir(&state).await?;
+    GameInstance::list_saves(&game_dir).map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 pub async fn get_version_manifest(
     client: State<'_, &api_client::MinecraftApiClient>,
@@ -14,7 +14,7 @@ use std::{
 };
 use tokio::sync::RwLock;
 
-#[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum ApiProvider {
     #[default]
@@ -22,36 +22,119 @@ pub enum ApiProvider {
     BMCLApi,
 }
 
-/// TODO)) 需要在下载时截取后面的部分才能正常使用第三方API
-/// 否则还是用的官方API
+impl ApiProvider {
+    /// every provider, in the order to fail over
+    pub const ALL: [ApiProvider; 2] = [ApiProvider::Official, ApiProvider::BMCLApi];
+}
+
+/// the official hosts and where BMCLAPI mirrors them
+const BMCLAPI_MIRRORS: &[(&str, &str)] = &[
+    (
+        "https://piston-meta.mojang.com/",
+        "https://bmclapi2.bangbang93.com/",
+    ),
+    (
+        "https://launchermeta.mojang.com/",
+        "https://bmclapi2.bangbang93.com/",
+    ),
+    (
+        "https://piston-data.mojang.com/",
+        "https://bmclapi2.bangbang93.com/",
+    ),
+    (
+        "https://launcher.mojang.com/",
+        "https://bmclapi2.bangbang93.com/",
+    ),
+    (
+        "https://libraries.minecraft.net/",
+        "https://bmclapi2.bangbang93.com/maven/",
+    ),
+    (
+        "https://resources.download.minecraft.net/",
+        "https://bmclapi2.bangbang93.com/assets/",
+    ),
+    (
+        "https://maven.minecraftforge.net/",
+        "https://bmclapi2.bangbang93.com/maven/",
+    ),
+    (
+        "https://files.minecraftforge.net/maven/",
+        "https://bmclapi2.bangbang93.com/maven/",
+    ),
+    (
+        "https://maven.neoforged.net/releases/",
+        "https://bmclapi2.bangbang93.com/maven/",
+    ),
+    (
+        "https://maven.fabricmc.net/",
+        "https://bmclapi2.bangbang93.com/maven/",
+    ),
+    (
+        "https://meta.fabricmc.net/",
+        "https://bmclapi2.bangbang93.com/fabric-meta/",
+    ),
+];
+
+/// the urls in version jsons always point to the official hosts,
+/// they are rewritten onto the provider with `mirror_url` when downloading
 #[derive(Clone)]
 pub struct ApiBases {
+    pub provider: ApiProvider,
     pub meta_base: &'static str,
-    pub resources_base: &'static str,
     pub forge_base: &'static str,
     pub fabric_base: &'static str,
     pub modrinth_base: &'static str,
+    pub authlib_injector_base: &'static str,
+    /// official url prefixes and their replacements
+    mirrors: &'static [(&'static str, &'static str)],
 }
 
 impl ApiBases {
     pub fn new(provider: &ApiProvider) -> Self {
         match provider {
             ApiProvider::Official => ApiBases {
+                provider: ApiProvider::Official,
                 meta_base: "https://piston-meta.mojang.com",
-                resources_base: "https://resources.download.minecraft.net",
                 forge_base: "https://maven.minecraftforge.net/net/minecraftforge/forge",
                 fabric_base: "https://meta.fabricmc.net/v2/versions/loader",
                 modrinth_base: "https://api.modrinth.com/v2",
+                authlib_injector_base: "https://authlib-injector.yushi.moe",
+                mirrors: &[],
             },
             ApiProvider::BMCLApi => ApiBases {
+                provider: ApiProvider::BMCLApi,
                 meta_base: "https://bmclapi2.bangbang93.com",
-                resources_base: "https://bmclapi2.bangbang93.com/assets",
                 forge_base: "https://bmclapi2.bangbang93.com/forge",
                 fabric_base: "https://bmclapi2.bangbang93.com/fabric-meta/v2/versions/loader",
                 modrinth_base: "https://api.modrinth.com/v2",
+                authlib_injector_base: "https://bmclapi2.bangbang93.com/mirrors/authlib-injector",
+                mirrors: BMCLAPI_MIRRORS,
             },
         }
     }
+
+    /// the url of an official file on this provider, urls of other hosts are left as they are
+    pub fn mirror_url(&self, url: &str) -> String {
+        self.mirrors
+            .iter()
+            .find_map(|(official, mirror)| {
+                url.strip_prefix(official)
+                    .map(|rest| format!("{}{}", mirror, rest))
+            })
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    /// this provider and then the others, to try one by one when a download fails
+    pub fn failover(&self) -> Vec<ApiBases> {
+        let mut bases = vec![self.clone()];
+        bases.extend(
+            ApiProvider::ALL
+                .iter()
+                .filter(|provider| **provider != self.provider)
+                .map(ApiBases::new),
+        );
+        bases
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -79,6 +162,16 @@ pub enum McApiError {
 
     #[error("plugin type {0:?} not supported")]
     PluginMismatch(super::mcmod::PluginType),
+
+    /// the task is paused or cancelled while downloading
+    #[error("download interrupted")]
+    Interrupted,
+
+    #[error("download cancelled")]
+    Cancelled,
+
+    #[error("invalid asset hash: {0}")]
+    InvalidAssetHash(String),
 }
 
 /// Result type for Minecraft API operations.
@@ -86,6 +179,10 @@ pub type McApiResult<T> = Result<T, McApiError>;
 
 pub mod game {
     use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    /// the maven repository of the libraries which don't give theirs
+    const MOJANG_LIBRARIES: &str = "https://libraries.minecraft.net/";
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct VersionManifest {
@@ -119,6 +216,113 @@ pub mod game {
         pub assets: String,
         pub asset_index: DownloadInfo,
         pub main_class: String,
+        /// modern argument format, since 17w43a
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub arguments: Option<Arguments>,
+        /// legacy argument format, a single string split by whitespace
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub minecraft_arguments: Option<String>,
+        /// java required by the version, missing before 1.7.10 of the new launcher
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub java_version: Option<JavaVersion>,
+    }
+
+    impl VersionDetails {
+        /// the java major version required, versions without declaration run on java 8
+        pub fn required_java(&self) -> i32 {
+            self.java_version
+                .as_ref()
+                .map_or(8, |java_version| java_version.major_version)
+        }
+
+        /// launchwrapper, used by legacy forge and optifine, breaks on a java newer than the
+        /// required one, which runs the other versions
+        pub fn requires_exact_java(&self) -> bool {
+            self.main_class == "net.minecraft.launchwrapper.Launch"
+                || self.main_class.starts_with("cpw.mods.")
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct JavaVersion {
+        #[serde(default)]
+        pub component: String,
+        pub major_version: i32,
+    }
+
+    /// the `arguments` object of a version json
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct Arguments {
+        #[serde(default)]
+        pub game: Vec<Argument>,
+        #[serde(default)]
+        pub jvm: Vec<Argument>,
+    }
+
+    /// an entry of `arguments.game` or `arguments.jvm`,
+    /// either a plain string or a value guarded by rules
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Argument {
+        Plain(String),
+        Conditional {
+            rules: Vec<Rule>,
+            value: ArgumentValue,
+        },
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum ArgumentValue {
+        Single(String),
+        Multiple(Vec<String>),
+    }
+
+    impl Argument {
+        /// the raw values of this argument if its rules allow, placeholders are not replaced
+        pub fn values(&self, features: &LaunchFeatures) -> Vec<&str> {
+            match self {
+                Argument::Plain(value) => vec![value.as_str()],
+                Argument::Conditional { rules, value } => {
+                    if !rules_allow(rules, features) {
+                        return Vec::new();
+                    }
+                    match value {
+                        ArgumentValue::Single(value) => vec![value.as_str()],
+                        ArgumentValue::Multiple(values) => {
+                            values.iter().map(String::as_str).collect()
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// features that could be checked by the `features` field of a rule
+    #[derive(Debug, Clone, Default)]
+    pub struct LaunchFeatures {
+        pub is_demo_user: bool,
+        pub has_custom_resolution: bool,
+        pub has_quick_plays_support: bool,
+        pub is_quick_play_singleplayer: bool,
+        pub is_quick_play_multiplayer: bool,
+        pub is_quick_play_realms: bool,
+    }
+
+    impl LaunchFeatures {
+        /// unknown features are treated as disabled
+        pub fn is_enabled(&self, feature: &str) -> bool {
+            match feature {
+                "is_demo_user" => self.is_demo_user,
+                "has_custom_resolution" => self.has_custom_resolution,
+                "has_quick_plays_support" => self.has_quick_plays_support,
+                "is_quick_play_singleplayer" => self.is_quick_play_singleplayer,
+                "is_quick_play_multiplayer" => self.is_quick_play_multiplayer,
+                "is_quick_play_realms" => self.is_quick_play_realms,
+                _ => false,
+            }
+        }
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,43 +339,264 @@ pub mod game {
         pub path: Option<String>,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum RuleAction {
+        Allow,
+        Disallow,
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct Rule {
-        pub action: String, // allow or not allow
-        pub os: OSRule,
+        pub action: RuleAction,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub os: Option<OSRule>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub features: Option<HashMap<String, bool>>,
     }
 
     impl Rule {
-        pub fn allow(&self) -> bool {
-            return self.action == "allow" && self.os.name.is_current();
+        /// whether the conditions of this rule match current environment,
+        /// a rule without any condition always matches
+        pub fn matches(&self, features: &LaunchFeatures) -> bool {
+            let os_match = self.os.as_ref().is_none_or(OSRule::matches);
+            let features_match = self.features.as_ref().is_none_or(|required| {
+                required
+                    .iter()
+                    .all(|(feature, value)| features.is_enabled(feature) == *value)
+            });
+            os_match && features_match
+        }
+    }
+
+    /// Evaluate rules the way Mojang does: everything is disallowed by default
+    /// once there are rules, and the last matched rule decides the action.
+    pub fn rules_allow(rules: &[Rule], features: &LaunchFeatures) -> bool {
+        if rules.is_empty() {
+            return true;
         }
+        rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(features))
+            .is_some_and(|rule| rule.action == RuleAction::Allow)
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct OSRule {
-        pub name: crate::core::platform::OS,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub name: Option<crate::core::platform::OS>,
+        /// regex matched against the os version
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub version: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub arch: Option<String>,
+    }
+
+    impl OSRule {
+        pub fn matches(&self) -> bool {
+            let name_match = self.name.as_ref().is_none_or(|name| name.is_current());
+            let arch_match = self.arch.as_deref().is_none_or(|arch| {
+                let current = tauri_plugin_os::arch();
+                arch == current
+                    || (arch == "amd64" && current == "x86_64")
+                    || (arch == "arm64" && current == "aarch64")
+            });
+            let version_match = self.version.as_deref().is_none_or(|pattern| {
+                regex::Regex::new(pattern)
+                    .map(|re| re.is_match(&tauri_plugin_os::version().to_string()))
+                    .unwrap_or_else(|err| {
+                        log::warn!("invalid os version regex {:?}: {}", pattern, err);
+                        false
+                    })
+            });
+            name_match && arch_match && version_match
+        }
     }
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct LibraryItem {
+        /// maven coordinate, `group:artifact:version[:classifier][@extension]`
         pub name: String,
+        /// libraries of mod loaders may only have a name and a maven repository url
+        #[serde(default)]
         pub downloads: LibraryDownloads,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub url: Option<String>,
+        /// given along with the maven repository url by some mod loaders
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub sha1: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub size: Option<u64>,
         pub rules: Option<Vec<Rule>>,
+        /// old-style natives, maps the os to a classifier in `downloads.classifiers`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub natives: Option<HashMap<crate::core::platform::OS, String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub extract: Option<ExtractRule>,
     }
 
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
     pub struct LibraryDownloads {
-        pub artifact: DownloadInfo,
+        /// natives-only libraries have no artifact
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub artifact: Option<DownloadInfo>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub classifiers: Option<HashMap<String, DownloadInfo>>,
+    }
+
+    /// paths in the natives jar that should not be extracted
+    #[derive(Debug, Clone, Serialize, Deserialize, Default)]
+    pub struct ExtractRule {
+        #[serde(default)]
+        pub exclude: Vec<String>,
+    }
+
+    /// the asset index json, listing all the asset objects of a version
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct AssetIndex {
+        pub objects: HashMap<String, AssetObject>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct AssetObject {
+        pub hash: String,
+        pub size: u64,
+    }
+
+    impl AssetObject {
+        /// objects are stored as `<first 2 chars of hash>/<hash>`,
+        /// the hash must be a sha1 since it becomes a path
+        pub fn download_info(&self) -> Result<DownloadInfo, super::McApiError> {
+            let prefix = self
+                .hash
+                .get(..2)
+                .filter(|_| {
+                    self.hash.len() == 40 && self.hash.chars().all(|c| c.is_ascii_hexdigit())
+                })
+                .ok_or_else(|| super::McApiError::InvalidAssetHash(self.hash.clone()))?;
+            let path = format!("{}/{}", prefix, self.hash);
+            Ok(DownloadInfo {
+                sha1: self.hash.clone(),
+                size: self.size,
+                url: format!("{}/{}", RESOURCES_BASE, path),
+                path: Some(path),
+            })
+        }
     }
 
     pub const VERSION_MANIFEST_ENDPOINT: &str = "mc/game/version_manifest.json";
+    /// the official host of the asset objects, rewritten onto the mirror when downloading
+    pub const RESOURCES_BASE: &str = "https://resources.download.minecraft.net";
 
     impl LibraryItem {
         pub fn rule_allow(&self) -> bool {
-            if let Some(rules) = &self.rules {
-                return rules.iter().any(|rule| rule.allow());
+            self.rules
+                .as_deref()
+                .is_none_or(|rules| rules_allow(rules, &LaunchFeatures::default()))
+        }
+
+        /// `group:artifact[:classifier]`, libraries of the same key are different versions of one library
+        pub fn maven_key(&self) -> String {
+            let coordinate = self.name.split('@').next().unwrap_or_default();
+            let parts: Vec<&str> = coordinate.split(':').collect();
+            match parts.as_slice() {
+                [group, artifact, _version, classifier, ..] => {
+                    format!("{group}:{artifact}:{classifier}")
+                }
+                [group, artifact, ..] => format!("{group}:{artifact}"),
+                _ => coordinate.to_string(),
+            }
+        }
+
+        /// the version in the maven coordinate
+        pub fn maven_version(&self) -> Option<&str> {
+            self.name.split('@').next()?.split(':').nth(2)
+        }
+
+        /// path under the libraries folder derived from the maven coordinate,
+        /// such as `org/ow2/asm/asm/9.8/asm-9.8.jar`
+        pub fn maven_path(&self) -> Option<String> {
+            let (coordinate, extension) = match self.name.split_once('@') {
+                Some((coordinate, extension)) => (coordinate, extension),
+                None => (self.name.as_str(), "jar"),
+            };
+            let mut parts = coordinate.split(':');
+            let group = parts.next()?;
+            let artifact = parts.next()?;
+            let version = parts.next()?;
+            let file_name = match parts.next() {
+                Some(classifier) => format!("{artifact}-{version}-{classifier}.{extension}"),
+                None => format!("{artifact}-{version}.{extension}"),
+            };
+            Some(format!(
+                "{}/{artifact}/{version}/{file_name}",
+                group.replace('.', "/")
+            ))
+        }
+
+        /// path of the jar put on classpath, under the libraries folder.
+        /// none for natives-only libraries, which are extracted instead
+        pub fn artifact_path(&self) -> Option<String> {
+            match &self.downloads.artifact {
+                Some(artifact) => artifact.path.clone().or_else(|| self.maven_path()),
+                None if self.natives.is_some() => None,
+                None => self.maven_path(),
             }
-            return true;
+        }
+
+        /// the natives classifier for current os, with `${arch}` replaced
+        pub fn native_classifier(&self) -> Option<String> {
+            let classifier = self
+                .natives
+                .as_ref()?
+                .get(&crate::core::platform::OS::current())?;
+            let arch = if cfg!(target_pointer_width = "64") {
+                "64"
+            } else {
+                "32"
+            };
+            Some(classifier.replace("${arch}", arch))
+        }
+
+        /// the natives jar to download and extract for current os, if there is
+        pub fn native_download(&self) -> Option<&DownloadInfo> {
+            let classifier = self.native_classifier()?;
+            self.downloads.classifiers.as_ref()?.get(&classifier)
+        }
+
+        /// the jar put on classpath to download, none for natives-only libraries.
+        /// a library with only a maven coordinate is fetched from its maven repository,
+        /// its sha1 is empty if not given
+        pub fn artifact_download(&self) -> Option<DownloadInfo> {
+            let path = self.artifact_path()?;
+            Some(match &self.downloads.artifact {
+                Some(artifact) => DownloadInfo {
+                    path: Some(path),
+                    ..artifact.clone()
+                },
+                None => DownloadInfo {
+                    sha1: self.sha1.clone().unwrap_or_default(),
+                    size: self.size.unwrap_or_default(),
+                    url: format!(
+                        "{}/{}",
+                        self.url
+                            .as_deref()
+                            .unwrap_or(MOJANG_LIBRARIES)
+                            .trim_end_matches('/'),
+                        path
+                    ),
+                    path: Some(path),
+                },
+            })
+        }
+
+        /// all the files of this library to download for current os
+        pub fn downloads_for_current(&self) -> Vec<DownloadInfo> {
+            self.artifact_download()
+                .into_iter()
+                .chain(self.native_download().cloned())
+                .collect()
         }
     }
 }
@@ -334,7 +759,8 @@ impl MinecraftApiClient {
             .iter()
             .find(|v| v.id == version_id)
             .ok_or(McApiError::VersionNotFound(version_id.to_string()))?;
-        let version_json = self.get::<serde_json::Value>(&version.url, false).await?;
+        let version_url = self.api_bases.read().await.mirror_url(&version.url);
+        let version_json = self.get::<serde_json::Value>(&version_url, false).await?;
         // save version detail json to temp_dir
         tokio::fs::create_dir_all(temp_dir).await?;
         let version_json_path = temp_dir.join(format!("{}.json", version_id));
@@ -452,6 +878,161 @@ mod tests {
         println!("{:?}", manifest);
     }
 
+    #[test]
+    fn asset_download_info() {
+        let object = game::AssetObject {
+            hash: "bdf48ef6b5d0d23bbb02e17d04865216179f510a".to_string(),
+            size: 1,
+        };
+        let info = object.download_info().unwrap();
+        assert_eq!(
+            info.path.as_deref(),
+            Some("bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a")
+        );
+        for hash in ["", "b", "../../../evil", "数据"] {
+            let object = game::AssetObject {
+                hash: hash.to_string(),
+                size: 1,
+            };
+            assert!(matches!(
+                object.download_info(),
+                Err(McApiError::InvalidAssetHash(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn version_arguments_parse() {
+        let arguments: game::Arguments = serde_json::from_str(
+            r#"{
+                "game": [
+                    "--username", "${auth_player_name}",
+                    {
+                        "rules": [{ "action": "allow", "features": { "is_demo_user": true } }],
+                        "value": "--demo"
+                    },
+                    {
+                        "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }],
+                        "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"]
+                    }
+                ],
+                "jvm": [
+                    { "rules": [{ "action": "allow", "os": { "arch": "x86" } }], "value": "-Xss1M" },
+                    "-cp", "${classpath}"
+                ]
+            }"#,
+        )
+        .unwrap();
+        let features = game::LaunchFeatures {
+            has_custom_resolution: true,
+            ..Default::default()
+        };
+        let game_args: Vec<&str> = arguments
+            .game
+            .iter()
+            .flat_map(|argument| argument.values(&features))
+            .collect();
+        assert_eq!(
+            game_args,
+            [
+                "--username",
+                "${auth_player_name}",
+                "--width",
+                "${resolution_width}",
+                "--height",
+                "${resolution_height}"
+            ]
+        );
+        assert_eq!(arguments.jvm.len(), 3);
+    }
+
+    #[test]
+    fn library_maven_path() {
+        let library: game::LibraryItem = serde_json::from_value(serde_json::json!({
+            "name": "net.fabricmc:sponge-mixin:0.15.5+mixin.0.8.7",
+            "url": "https://maven.fabricmc.net/"
+        }))
+        .unwrap();
+        assert_eq!(
+            library.artifact_path().unwrap(),
+            "net/fabricmc/sponge-mixin/0.15.5+mixin.0.8.7/sponge-mixin-0.15.5+mixin.0.8.7.jar"
+        );
+        assert_eq!(library.maven_key(), "net.fabricmc:sponge-mixin");
+        let download = library.artifact_download().unwrap();
+        assert_eq!(
+            download.url,
+            "https://maven.fabricmc.net/net/fabricmc/sponge-mixin/0.15.5+mixin.0.8.7/sponge-mixin-0.15.5+mixin.0.8.7.jar"
+        );
+        assert_eq!(download.path, library.artifact_path());
+        let native: game::LibraryItem = serde_json::from_value(serde_json::json!({
+            "name": "org.lwjgl:lwjgl:3.3.3:natives-windows@zip",
+        }))
+        .unwrap();
+        assert_eq!(
+            native.maven_path().unwrap(),
+            "org/lwjgl/lwjgl/3.3.3/lwjgl-3.3.3-natives-windows.zip"
+        );
+        assert_eq!(native.maven_key(), "org.lwjgl:lwjgl:natives-windows");
+        let natives_only: game::LibraryItem = serde_json::from_value(serde_json::json!({
+            "name": "org.lwjgl.lwjgl:lwjgl-platform:2.9.4",
+            "natives": { "linux": "natives-linux" },
+            "downloads": { "classifiers": {} }
+        }))
+        .unwrap();
+        assert!(natives_only.artifact_path().is_none());
+    }
+
+    #[test]
+    fn mirror_url_rewrite() {
+        let bmclapi = ApiBases::new(&ApiProvider::BMCLApi);
+        assert_eq!(
+            bmclapi.mirror_url(
+                "https://libraries.minecraft.net/com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar"
+            ),
+            "https://bmclapi2.bangbang93.com/maven/com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar"
+        );
+        assert_eq!(
+            bmclapi.mirror_url("https://resources.download.minecraft.net/ab/abcdef"),
+            "https://bmclapi2.bangbang93.com/assets/ab/abcdef"
+        );
+        assert_eq!(
+            bmclapi.mirror_url("https://example.com/file.jar"),
+            "https://example.com/file.jar"
+        );
+        let failover = bmclapi.failover();
+        assert!(failover[0].provider == ApiProvider::BMCLApi);
+        assert!(failover[1].provider == ApiProvider::Official);
+        assert_eq!(
+            failover[1].mirror_url("https://piston-data.mojang.com/v1/objects/abc/client.jar"),
+            "https://piston-data.mojang.com/v1/objects/abc/client.jar"
+        );
+    }
+
+    #[test]
+    fn rules_last_match_wins() {
+        use crate::core::platform::OS;
+        let features = game::LaunchFeatures::default();
+        let current = serde_json::to_value(OS::current()).unwrap();
+        let rules: Vec<game::Rule> = serde_json::from_value(serde_json::json!([
+            { "action": "allow" },
+            { "action": "disallow", "os": { "name": current } }
+        ]))
+        .unwrap();
+        assert!(!game::rules_allow(&rules, &features));
+        assert!(game::rules_allow(&rules[..1], &features));
+        let only_current: Vec<game::Rule> = serde_json::from_value(serde_json::json!([
+            { "action": "allow", "os": { "name": current, "version": "^definitely-not-a-version$" } }
+        ]))
+        .unwrap();
+        assert!(!game::rules_allow(&only_current, &features));
+        let demo: Vec<game::Rule> = serde_json::from_value(serde_json::json!([
+            { "action": "allow", "features": { "is_demo_user": true } }
+        ]))
+        .unwrap();
+        assert!(!game::rules_allow(&demo, &features));
+        assert!(game::rules_allow(&[], &features));
+    }
+
     #[test]
     fn forge_test() {
         let mc_api_client = &ConfigManager::instance().api_client;
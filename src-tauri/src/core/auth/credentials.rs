@@ -0,0 +1,270 @@
+//! Tokens of the accounts, kept out of `config.json` and encrypted at rest.
+//! The key is derived from an identifier of the machine which the launcher never writes down,
+//! so the file can't be used on another machine nor with the files next to it.
+//! It is bound to the machine, not hidden from its users: on linux the identifier may be
+//! `/etc/machine-id`, which every local user can read.
+//! `config.json` keeps the accounts with blank tokens, which are filled back when loading
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::Serializer;
+
+use crate::{core::auth::Account, util::crypto};
+
+const FILE_NAME: &str = "credentials.dat";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CredentialsError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Json parsing failed: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Crypto(#[from] anyhow::Error),
+}
+
+/// the secrets of an account
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Credentials {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    client_token: Option<String>,
+}
+
+impl Account {
+    /// the key the credentials are stored with
+    fn credentials_key(&self) -> String {
+        format!("{}:{}", self.provider(), self.uuid())
+    }
+
+    /// the secrets of the account, offline accounts have none
+    fn credentials(&self) -> Option<Credentials> {
+        match self {
+            Account::Offline { .. } => None,
+            Account::Microsoft {
+                access_token,
+                refresh_token,
+                ..
+            } => Some(Credentials {
+                access_token: access_token.clone(),
+                refresh_token: Some(refresh_token.clone()),
+                client_token: None,
+            }),
+            Account::Yggdrasil {
+                access_token,
+                client_token,
+                ..
+            }
+            | Account::AuthlibInjector {
+                access_token,
+                client_token,
+                ..
+            } => Some(Credentials {
+                access_token: access_token.clone(),
+                refresh_token: None,
+                client_token: client_token.clone(),
+            }),
+        }
+    }
+
+    fn set_credentials(&mut self, credentials: Credentials) {
+        match self {
+            Account::Offline { .. } => {}
+            Account::Microsoft {
+                access_token,
+                refresh_token,
+                ..
+            } => {
+                *access_token = credentials.access_token;
+                *refresh_token = credentials.refresh_token.unwrap_or_default();
+            }
+            Account::Yggdrasil {
+                access_token,
+                client_token,
+                ..
+            }
+            | Account::AuthlibInjector {
+                access_token,
+                client_token,
+                ..
+            } => {
+                *access_token = credentials.access_token;
+                *client_token = credentials.client_token;
+            }
+        }
+    }
+
+    /// the account as written to `config.json`
+    fn without_credentials(&self) -> Account {
+        let mut account = self.clone();
+        account.set_credentials(Credentials::default());
+        account
+    }
+
+    /// whether the token is in the account itself, which is written in plain text by old versions
+    fn has_plaintext_credentials(&self) -> bool {
+        self.access_token().is_some_and(|token| !token.is_empty())
+    }
+}
+
+/// the encrypted file of the credentials
+pub struct CredentialStore {
+    path: PathBuf,
+    /// none if the machine has no secret, then the tokens last for the session only
+    key: Option<[u8; 32]>,
+}
+
+impl CredentialStore {
+    pub fn new(config_dir: &Path, machine_secret: Option<&str>) -> Self {
+        Self {
+            path: config_dir.join(FILE_NAME),
+            key: machine_secret.map(crypto::derive_key),
+        }
+    }
+
+    fn read(&self, key: &[u8; 32]) -> Result<HashMap<String, Credentials>, CredentialsError> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+        let plaintext = crypto::decrypt(key, &std::fs::read(&self.path)?)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// move the file away so the next save doesn't overwrite it,
+    /// it may be read again on the machine it was written on
+    fn set_aside(&self) -> Result<PathBuf, CredentialsError> {
+        let aside = self.path.with_file_name(format!(
+            "credentials.{}.dat",
+            chrono::Local::now().format("%Y%m%d%H%M%S")
+        ));
+        std::fs::rename(&self.path, &aside)?;
+        Ok(aside)
+    }
+
+    /// write the credentials of all the accounts, replacing the ones saved before
+    pub fn save<'a>(
+        &self,
+        accounts: impl IntoIterator<Item = &'a Account>,
+    ) -> Result<(), CredentialsError> {
+        let Some(key) = &self.key else {
+            log::warn!("no machine secret to encrypt the credentials, they are not saved");
+            return Ok(());
+        };
+        let credentials: HashMap<String, Credentials> = accounts
+            .into_iter()
+            .filter_map(|account| Some((account.credentials_key(), account.credentials()?)))
+            .collect();
+        let ciphertext = crypto::encrypt(key, &serde_json::to_vec(&credentials)?)?;
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, ciphertext)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&temp_path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// fill the tokens of the accounts loaded from `config.json`.
+    /// returns whether any token was found in plain text, which should be saved again to migrate.
+    /// a file which can't be decrypted is set aside, the accounts have to log in again
+    pub fn restore(
+        &self,
+        accounts: &mut [Account],
+        active_account: &mut Option<Arc<Account>>,
+    ) -> Result<bool, CredentialsError> {
+        let mut plaintext = false;
+        let credentials = match self.key.as_ref().map(|key| self.read(key)) {
+            Some(Ok(credentials)) => credentials,
+            Some(Err(err @ (CredentialsError::Crypto(_) | CredentialsError::Json(_)))) => {
+                let aside = self.set_aside()?;
+                log::warn!("credentials unreadable, moved to {:?}: {}", aside, err);
+                HashMap::new()
+            }
+            Some(Err(err)) => return Err(err),
+            None => HashMap::new(),
+        };
+        let accounts = accounts
+            .iter_mut()
+            .chain(active_account.as_mut().map(Arc::make_mut));
+        for account in accounts {
+            if account.has_plaintext_credentials() {
+                plaintext = true;
+            } else if let Some(credentials) = credentials.get(&account.credentials_key()) {
+                account.set_credentials(credentials.clone());
+            }
+        }
+        Ok(plaintext)
+    }
+}
+
+/// `serialize_with` of `AppState::accounts`, the tokens are left out
+pub fn serialize_accounts<S: Serializer>(
+    accounts: &[Account],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(accounts.iter().map(Account::without_credentials))
+}
+
+/// `serialize_with` of `AppState::active_account`, the tokens are left out
+pub fn serialize_active_account<S: Serializer>(
+    account: &Option<Arc<Account>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(
+        &account.as_deref().map(Account::without_credentials),
+        serializer,
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn credentials_test() {
+    let dir = std::env::temp_dir().join("pcl-proto-credentials-test");
+    // a failed run may leave its files
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let store = CredentialStore::new(&dir, Some("a98f501bc684032f"));
+    let account = Account::Yggdrasil {
+        username: "Steve".to_string(),
+        uuid: "0123".to_string(),
+        access_token: "secret".to_string(),
+        client_token: Some("client".to_string()),
+        user_properties: Default::default(),
+    };
+    store.save([&account]).unwrap();
+    assert!(
+        !String::from_utf8_lossy(&std::fs::read(dir.join(FILE_NAME)).unwrap()).contains("secret")
+    );
+
+    let mut accounts = vec![account.without_credentials()];
+    let mut active_account = Some(Arc::new(account.without_credentials()));
+    assert!(!store.restore(&mut accounts, &mut active_account).unwrap());
+    assert_eq!(accounts[0].access_token(), Some("secret"));
+    assert_eq!(active_account.unwrap().access_token(), Some("secret"));
+    // tokens of old configs in plain text are kept and reported
+    assert!(store.restore(&mut [account.clone()], &mut None).unwrap());
+
+    // the file of another machine is kept aside rather than overwritten
+    let other = CredentialStore::new(&dir, Some("another machine"));
+    let mut accounts = vec![account.without_credentials()];
+    assert!(!other.restore(&mut accounts, &mut None).unwrap());
+    assert_eq!(accounts[0].access_token(), Some(""));
+    assert!(!dir.join(FILE_NAME).exists());
+    other.save([&account]).unwrap();
+    let aside = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name() != FILE_NAME)
+        .count();
+    assert_eq!(aside, 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,605 @@
+//! Microsoft account login with the OAuth device code flow.
+//! microsoft identity -> xbox live -> xsts -> minecraft services,
+//! see [Microsoft Authentication Scheme](https://minecraft.wiki/w/Microsoft_authentication)
+use std::time::Duration;
+
+use base64::Engine;
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use crate::{core::auth::Account, setup::constants::USER_AGENT};
+
+/// client id of the azure application, given at build time
+const DEFAULT_CLIENT_ID: &str = match option_env!("PCL_MICROSOFT_CLIENT_ID") {
+    Some(client_id) => client_id,
+    None => "",
+};
+
+/// endpoints and client id of the login flow, replaceable to test against a mock server.
+/// only what the user sets is kept, so the config follows the defaults of the launcher
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MicrosoftAuthConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_code_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xbox_auth_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xsts_auth_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minecraft_login_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entitlements_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_url: Option<String>,
+}
+
+/// the value set by the user, an empty one is left by old versions writing the defaults
+fn or_default<'a>(value: &'a Option<String>, default: &'a str) -> &'a str {
+    value
+        .as_deref()
+        .filter(|value| !value.is_empty())
+        .unwrap_or(default)
+}
+
+impl MicrosoftAuthConfig {
+    pub fn client_id(&self) -> &str {
+        or_default(&self.client_id, DEFAULT_CLIENT_ID)
+    }
+
+    pub fn scope(&self) -> &str {
+        or_default(&self.scope, "XboxLive.signin offline_access")
+    }
+
+    pub fn device_code_url(&self) -> &str {
+        or_default(
+            &self.device_code_url,
+            "https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode",
+        )
+    }
+
+    pub fn token_url(&self) -> &str {
+        or_default(
+            &self.token_url,
+            "https://login.microsoftonline.com/consumers/oauth2/v2.0/token",
+        )
+    }
+
+    pub fn xbox_auth_url(&self) -> &str {
+        or_default(
+            &self.xbox_auth_url,
+            "https://user.auth.xboxlive.com/user/authenticate",
+        )
+    }
+
+    pub fn xsts_auth_url(&self) -> &str {
+        or_default(
+            &self.xsts_auth_url,
+            "https://xsts.auth.xboxlive.com/xsts/authorize",
+        )
+    }
+
+    pub fn minecraft_login_url(&self) -> &str {
+        or_default(
+            &self.minecraft_login_url,
+            "https://api.minecraftservices.com/authentication/login_with_xbox",
+        )
+    }
+
+    pub fn entitlements_url(&self) -> &str {
+        or_default(
+            &self.entitlements_url,
+            "https://api.minecraftservices.com/entitlements/mcstore",
+        )
+    }
+
+    pub fn profile_url(&self) -> &str {
+        or_default(
+            &self.profile_url,
+            "https://api.minecraftservices.com/minecraft/profile",
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MicrosoftAuthError {
+    #[error("HTTP request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("no client id of microsoft login is configured")]
+    MissingClientId,
+
+    #[error("the device code expired before the user logged in")]
+    DeviceCodeExpired,
+
+    #[error("the user declined the login")]
+    AuthorizationDeclined,
+
+    #[error("oauth error {error}: {description}")]
+    OAuthError { error: String, description: String },
+
+    #[error("the microsoft account has no xbox account, please sign up for xbox first")]
+    NoXboxAccount,
+
+    #[error("xbox live is not available in the country of the account")]
+    XboxUnavailable,
+
+    #[error("the account needs adult verification on the xbox page")]
+    AdultVerificationRequired,
+
+    #[error("the account is a child account, it must be added to a family by an adult")]
+    ChildAccount,
+
+    #[error("xsts authorization failed with XErr {0}")]
+    XstsError(u64),
+
+    #[error("the account doesn't own minecraft")]
+    NotOwned,
+
+    #[error(
+        "the account owns minecraft but has no profile yet, please create one on minecraft.net"
+    )]
+    NoProfile,
+
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+}
+
+/// shown to the user, who should open the uri and enter the code
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    /// in seconds
+    pub expires_in: u64,
+    /// seconds to wait between polling
+    pub interval: u64,
+    pub message: String,
+}
+
+/// tokens of microsoft identity
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct OAuthToken {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(serde::Deserialize)]
+struct OAuthErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XboxToken {
+    token: String,
+    display_claims: XboxDisplayClaims,
+}
+
+#[derive(serde::Deserialize)]
+struct XboxDisplayClaims {
+    xui: Vec<XboxUserInfo>,
+}
+
+#[derive(serde::Deserialize)]
+struct XboxUserInfo {
+    uhs: String,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XstsErrorResponse {
+    x_err: u64,
+}
+
+/// the minecraft access token
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct MinecraftToken {
+    pub access_token: String,
+    /// in seconds
+    pub expires_in: i64,
+}
+
+#[derive(serde::Deserialize)]
+struct Entitlements {
+    #[serde(default)]
+    items: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct MinecraftProfile {
+    pub id: String,
+    pub name: String,
+}
+
+pub struct MicrosoftAuth {
+    client: Client,
+    config: MicrosoftAuthConfig,
+}
+
+impl MicrosoftAuth {
+    pub fn new(config: MicrosoftAuthConfig) -> Self {
+        Self {
+            client: Client::builder().user_agent(USER_AGENT).build().unwrap(),
+            config,
+        }
+    }
+
+    /// start the device code flow, the code should be shown to the user
+    pub async fn request_device_code(&self) -> Result<DeviceCode, MicrosoftAuthError> {
+        if self.config.client_id().is_empty() {
+            return Err(MicrosoftAuthError::MissingClientId);
+        }
+        let response = self
+            .client
+            .post(self.config.device_code_url())
+            .form(&[
+                ("client_id", self.config.client_id()),
+                ("scope", self.config.scope()),
+            ])
+            .send()
+            .await?;
+        parse_oauth_response(response).await
+    }
+
+    /// poll until the user finishes logging in on the verification page
+    pub async fn poll_token(
+        &self,
+        device_code: &DeviceCode,
+    ) -> Result<OAuthToken, MicrosoftAuthError> {
+        let mut interval = device_code.interval.max(1);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(device_code.expires_in);
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            if tokio::time::Instant::now() > deadline {
+                return Err(MicrosoftAuthError::DeviceCodeExpired);
+            }
+            let response = self
+                .client
+                .post(self.config.token_url())
+                .form(&[
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                    ("client_id", self.config.client_id()),
+                    ("device_code", device_code.device_code.as_str()),
+                ])
+                .send()
+                .await?;
+            match parse_oauth_response(response).await {
+                Err(MicrosoftAuthError::OAuthError { error, .. })
+                    if error == "authorization_pending" => {}
+                Err(MicrosoftAuthError::OAuthError { error, .. }) if error == "slow_down" => {
+                    interval += 5;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// xbox live user token and user hash
+    async fn authenticate_xbox(
+        &self,
+        ms_access_token: &str,
+    ) -> Result<XboxToken, MicrosoftAuthError> {
+        let response = self
+            .client
+            .post(self.config.xbox_auth_url())
+            .json(&json!({
+                "Properties": {
+                    "AuthMethod": "RPS",
+                    "SiteName": "user.auth.xboxlive.com",
+                    "RpsTicket": format!("d={}", ms_access_token)
+                },
+                "RelyingParty": "http://auth.xboxlive.com",
+                "TokenType": "JWT"
+            }))
+            .send()
+            .await?;
+        parse_json(response.error_for_status()?).await
+    }
+
+    async fn authorize_xsts(&self, xbox_token: &str) -> Result<XboxToken, MicrosoftAuthError> {
+        let response = self
+            .client
+            .post(self.config.xsts_auth_url())
+            .json(&json!({
+                "Properties": {
+                    "SandboxId": "RETAIL",
+                    "UserTokens": [xbox_token]
+                },
+                "RelyingParty": "rp://api.minecraftservices.com/",
+                "TokenType": "JWT"
+            }))
+            .send()
+            .await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            let error: XstsErrorResponse = parse_json(response).await?;
+            return Err(xsts_error(error.x_err));
+        }
+        parse_json(response.error_for_status()?).await
+    }
+
+    async fn login_minecraft(
+        &self,
+        xsts: &XboxToken,
+    ) -> Result<MinecraftToken, MicrosoftAuthError> {
+        let user_hash = &xsts
+            .display_claims
+            .xui
+            .first()
+            .ok_or_else(|| MicrosoftAuthError::InvalidResponse("no user hash in xsts".into()))?
+            .uhs;
+        let response = self
+            .client
+            .post(self.config.minecraft_login_url())
+            .json(&json!({
+                "identityToken": format!("XBL3.0 x={};{}", user_hash, xsts.token)
+            }))
+            .send()
+            .await?;
+        parse_json(response.error_for_status()?).await
+    }
+
+    /// whether the account owns the game, game pass owners may have no entitlement
+    async fn owns_game(&self, mc_access_token: &str) -> Result<bool, MicrosoftAuthError> {
+        let response = self
+            .client
+            .get(self.config.entitlements_url())
+            .bearer_auth(mc_access_token)
+            .send()
+            .await?;
+        let entitlements: Entitlements = parse_json(response.error_for_status()?).await?;
+        Ok(!entitlements.items.is_empty())
+    }
+
+    pub async fn fetch_profile(
+        &self,
+        mc_access_token: &str,
+    ) -> Result<Option<MinecraftProfile>, MicrosoftAuthError> {
+        let response = self
+            .client
+            .get(self.config.profile_url())
+            .bearer_auth(mc_access_token)
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(parse_json(response.error_for_status()?).await?))
+    }
+
+    /// the rest of the chain after microsoft identity, which refreshing shares
+    pub async fn login_with_oauth_token(
+        &self,
+        oauth_token: &OAuthToken,
+    ) -> Result<Account, MicrosoftAuthError> {
+        let xbox = self.authenticate_xbox(&oauth_token.access_token).await?;
+        let xsts = self.authorize_xsts(&xbox.token).await?;
+        let minecraft = self.login_minecraft(&xsts).await?;
+        let owns_game = self.owns_game(&minecraft.access_token).await?;
+        let profile = match self.fetch_profile(&minecraft.access_token).await? {
+            Some(profile) => profile,
+            None if owns_game => return Err(MicrosoftAuthError::NoProfile),
+            None => return Err(MicrosoftAuthError::NotOwned),
+        };
+        log::info!("logged in microsoft account {}", profile.name);
+        Ok(Account::Microsoft {
+            username: profile.name,
+            uuid: profile.id,
+            xuid: xuid_of_token(&minecraft.access_token).unwrap_or_default(),
+            access_token: minecraft.access_token,
+            refresh_token: oauth_token.refresh_token.clone(),
+            client_id: Some(self.config.client_id().to_string()),
+            expires_at: Some(chrono::Local::now().timestamp_millis() + minecraft.expires_in * 1000),
+        })
+    }
+
+    /// log in again with the refresh token, without the user
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Account, MicrosoftAuthError> {
+        let response = self
+            .client
+            .post(self.config.token_url())
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", self.config.client_id()),
+                ("refresh_token", refresh_token),
+                ("scope", self.config.scope()),
+            ])
+            .send()
+            .await?;
+        let oauth_token: OAuthToken = parse_oauth_response(response).await?;
+        self.login_with_oauth_token(&oauth_token).await
+    }
+
+    /// the whole device code flow, `on_device_code` is called once the code is ready to show
+    pub async fn login(
+        &self,
+        on_device_code: impl FnOnce(&DeviceCode),
+    ) -> Result<Account, MicrosoftAuthError> {
+        let device_code = self.request_device_code().await?;
+        on_device_code(&device_code);
+        let oauth_token = self.poll_token(&device_code).await?;
+        self.login_with_oauth_token(&oauth_token).await
+    }
+}
+
+async fn parse_json<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T, MicrosoftAuthError> {
+    let text = response.text().await?;
+    serde_json::from_str(&text).map_err(|err| MicrosoftAuthError::InvalidResponse(err.to_string()))
+}
+
+/// oauth endpoints respond errors in json with a 400 status
+async fn parse_oauth_response<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T, MicrosoftAuthError> {
+    if response.status().is_success() {
+        return parse_json(response).await;
+    }
+    let error: OAuthErrorResponse = parse_json(response).await?;
+    Err(match error.error.as_str() {
+        "expired_token" => MicrosoftAuthError::DeviceCodeExpired,
+        "authorization_declined" | "access_denied" => MicrosoftAuthError::AuthorizationDeclined,
+        _ => MicrosoftAuthError::OAuthError {
+            error: error.error,
+            description: error.error_description,
+        },
+    })
+}
+
+/// see [XErr codes](https://wiki.vg/Microsoft_Authentication_Scheme#Authenticate_with_XSTS)
+fn xsts_error(x_err: u64) -> MicrosoftAuthError {
+    match x_err {
+        2148916233 => MicrosoftAuthError::NoXboxAccount,
+        2148916235 => MicrosoftAuthError::XboxUnavailable,
+        2148916236 | 2148916237 => MicrosoftAuthError::AdultVerificationRequired,
+        2148916238 => MicrosoftAuthError::ChildAccount,
+        _ => MicrosoftAuthError::XstsError(x_err),
+    }
+}
+
+/// the minecraft access token is a jwt carrying the xuid
+fn xuid_of_token(mc_access_token: &str) -> Option<String> {
+    let payload = mc_access_token.split('.').nth(1)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    claims["xuid"].as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+#[test]
+fn microsoft_auth_responses() {
+    assert!(matches!(
+        xsts_error(2148916233),
+        MicrosoftAuthError::NoXboxAccount
+    ));
+    assert!(matches!(
+        xsts_error(2148916238),
+        MicrosoftAuthError::ChildAccount
+    ));
+    assert!(matches!(xsts_error(1), MicrosoftAuthError::XstsError(1)));
+    let xsts: XboxToken = serde_json::from_str(
+        r#"{"IssueInstant":"2020-12-07T19:52:08.4463796Z","Token":"token","DisplayClaims":{"xui":[{"uhs":"userhash"}]}}"#,
+    )
+    .unwrap();
+    assert_eq!(xsts.display_claims.xui[0].uhs, "userhash");
+    // header and signature don't matter
+    let payload =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"xuid":"2535405290000000"}"#);
+    assert_eq!(
+        xuid_of_token(&format!("header.{payload}.signature")).unwrap(),
+        "2535405290000000"
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn microsoft_login_flow() {
+    use crate::util::mock_server::{self, Response};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let payload =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"xuid":"2535405290000000"}"#);
+    let mc_token = format!("header.{payload}.signature");
+    let bearer = format!("Bearer {mc_token}");
+    let pending = AtomicBool::new(true);
+    let base = mock_server::serve(move |request| {
+        let body = String::from_utf8_lossy(&request.body);
+        let authorized = request.headers.get("authorization") == Some(&bearer);
+        match request.path.as_str() {
+            "/devicecode" => Response::json(
+                200,
+                json!({
+                    "device_code": "device",
+                    "user_code": "ABCD1234",
+                    "verification_uri": "https://www.microsoft.com/link",
+                    "expires_in": 900,
+                    "interval": 1,
+                    "message": "enter the code"
+                }),
+            ),
+            // the user logs in after the first poll
+            "/token" if pending.swap(false, Ordering::Relaxed) => {
+                Response::json(400, json!({ "error": "authorization_pending" }))
+            }
+            "/token" if body.contains("device") => Response::json(
+                200,
+                json!({ "access_token": "ms-token", "refresh_token": "ms-refresh" }),
+            ),
+            "/xbox" if body.contains("d=ms-token") => Response::json(
+                200,
+                json!({ "Token": "xbl-token", "DisplayClaims": { "xui": [{ "uhs": "userhash" }] } }),
+            ),
+            "/xsts" if body.contains("xbl-token") => Response::json(
+                200,
+                json!({ "Token": "xsts-token", "DisplayClaims": { "xui": [{ "uhs": "userhash" }] } }),
+            ),
+            "/login" if body.contains("XBL3.0 x=userhash;xsts-token") => Response::json(
+                200,
+                json!({ "access_token": mc_token, "expires_in": 86400 }),
+            ),
+            "/entitlements" if authorized && request.method == "GET" => {
+                Response::json(200, json!({ "items": [{ "name": "game_minecraft" }] }))
+            }
+            "/profile" if authorized => Response::json(200, json!({ "id": "0123", "name": "Steve" })),
+            _ => Response::new(401, ""),
+        }
+    })
+    .await;
+    let config = MicrosoftAuthConfig {
+        client_id: Some("client".to_string()),
+        device_code_url: Some(format!("{base}/devicecode")),
+        token_url: Some(format!("{base}/token")),
+        xbox_auth_url: Some(format!("{base}/xbox")),
+        xsts_auth_url: Some(format!("{base}/xsts")),
+        minecraft_login_url: Some(format!("{base}/login")),
+        entitlements_url: Some(format!("{base}/entitlements")),
+        profile_url: Some(format!("{base}/profile")),
+        ..Default::default()
+    };
+    let mut user_code = None;
+    let account = MicrosoftAuth::new(config)
+        .login(|device_code| user_code = Some(device_code.user_code.clone()))
+        .await
+        .unwrap();
+    assert_eq!(user_code.as_deref(), Some("ABCD1234"));
+    let Account::Microsoft {
+        username,
+        uuid,
+        access_token,
+        refresh_token,
+        xuid,
+        client_id,
+        ..
+    } = account
+    else {
+        panic!("not a microsoft account");
+    };
+    assert_eq!((username.as_str(), uuid.as_str()), ("Steve", "0123"));
+    assert_eq!(access_token, format!("header.{payload}.signature"));
+    assert_eq!(refresh_token, "ms-refresh");
+    assert_eq!(xuid, "2535405290000000");
+    assert_eq!(client_id.as_deref(), Some("client"));
+
+    // only the overrides are written to the config, the empty ones of old configs are ignored
+    assert_eq!(
+        serde_json::to_value(MicrosoftAuthConfig::default()).unwrap(),
+        json!({})
+    );
+    let config: MicrosoftAuthConfig =
+        serde_json::from_value(json!({ "client_id": "", "scope": "XboxLive.signin" })).unwrap();
+    assert_eq!(config.client_id(), DEFAULT_CLIENT_ID);
+    assert_eq!(config.scope(), "XboxLive.signin");
+}
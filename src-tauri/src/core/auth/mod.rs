@@ -0,0 +1,335 @@
+pub mod credentials;
+pub mod microsoft;
+pub mod offline;
+pub mod yggdrasil;
+
+use std::collections::HashMap;
+
+use microsoft::{MicrosoftAuth, MicrosoftAuthConfig, MicrosoftAuthError};
+use yggdrasil::{MOJANG_AUTH_SERVER, YggdrasilClient, YggdrasilError};
+
+/// tokens expiring within this are refreshed before launching, in milliseconds
+const EXPIRY_MARGIN_MS: i64 = 5 * 60 * 1000;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub enum Account {
+    Offline {
+        username: String,
+        uuid: String,
+    },
+    Yggdrasil {
+        username: String,
+        uuid: String,
+        access_token: String,
+        #[serde(default)]
+        client_token: Option<String>,
+        /// the properties of the user, such as the preferred language
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        user_properties: HashMap<String, Vec<String>>,
+    },
+    Microsoft {
+        username: String,
+        uuid: String,
+        access_token: String,
+        refresh_token: String,
+        xuid: String,
+        /// client id of the azure application the account logged in with
+        #[serde(default)]
+        client_id: Option<String>,
+        /// when the access token expires, unix timestamp in milliseconds
+        #[serde(default)]
+        expires_at: Option<i64>,
+    },
+    /// 外置登录（Authlib-Injector）
+    AuthlibInjector {
+        /// 第三方登录 API 提供方的 API 链接，登录的请求将通过这个 API 发送
+        api_location: String,
+        /// 第三方登录 API 提供方的服务器名称，用于 GUI 显示
+        server_name: String,
+        /// 第三方登录 API 提供方的网页主页，用于 GUI 显示跳转
+        server_homepage: String,
+        /// 第三方登录 API 提供方的元数据，需要在启动时携带这个作为参数
+        server_meta: String,
+        /// 第三方登录令牌，将会作为启动参数的一部分传入游戏实例
+        access_token: String,
+        /// 与令牌绑定的客户端令牌，刷新令牌时需要
+        #[serde(default)]
+        client_token: Option<String>,
+        /// 第三方正版玩家的统一标识
+        uuid: String,
+        /// 第三方正版玩家的名称
+        username: String,
+        /// 第三方登录服务器返回的用户属性
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        user_properties: HashMap<String, Vec<String>>,
+    },
+}
+
+impl Account {
+    pub fn username(&self) -> &str {
+        match self {
+            Account::Offline { username, .. } => username,
+            Account::Yggdrasil { username, .. } => username,
+            Account::Microsoft { username, .. } => username,
+            Account::AuthlibInjector { username, .. } => username,
+        }
+    }
+
+    pub fn uuid(&self) -> &str {
+        match self {
+            Account::Offline { uuid, .. } => uuid,
+            Account::Yggdrasil { uuid, .. } => uuid,
+            Account::Microsoft { uuid, .. } => uuid,
+            Account::AuthlibInjector { uuid, .. } => uuid,
+        }
+    }
+
+    /// where the account logs in, the api location for authlib-injector accounts
+    pub fn provider(&self) -> &str {
+        match self {
+            Account::Offline { .. } => "offline",
+            Account::Yggdrasil { .. } => "mojang",
+            Account::Microsoft { .. } => "microsoft",
+            Account::AuthlibInjector { api_location, .. } => api_location,
+        }
+    }
+
+    /// the same player of the same provider, maybe with another token
+    pub fn is_same(&self, other: &Account) -> bool {
+        self.uuid().eq_ignore_ascii_case(other.uuid()) && self.provider() == other.provider()
+    }
+
+    // 对于只有部分变体有的字段，返回 Option
+    pub fn access_token(&self) -> Option<&str> {
+        match self {
+            Account::Offline { .. } => None,
+            Account::Yggdrasil { access_token, .. } => Some(access_token),
+            Account::Microsoft { access_token, .. } => Some(access_token),
+            Account::AuthlibInjector { access_token, .. } => Some(access_token),
+        }
+    }
+
+    /// `${user_type}` of the launch arguments
+    pub fn user_type(&self) -> &'static str {
+        match self {
+            Account::Offline { .. } => "legacy",
+            Account::Yggdrasil { .. } | Account::AuthlibInjector { .. } => "mojang",
+            Account::Microsoft { .. } => "msa",
+        }
+    }
+
+    /// `${user_properties}` of the launch arguments, a json object of the property values by name.
+    /// legacy versions crash on an empty string, so it is `{}` for the accounts without properties
+    pub fn user_properties(&self) -> String {
+        match self {
+            Account::Yggdrasil {
+                user_properties, ..
+            }
+            | Account::AuthlibInjector {
+                user_properties, ..
+            } => serde_json::to_string(user_properties).unwrap_or_else(|_| "{}".to_string()),
+            _ => "{}".to_string(),
+        }
+    }
+
+    /// xbox user id, only microsoft accounts have
+    pub fn xuid(&self) -> Option<&str> {
+        match self {
+            Account::Microsoft { xuid, .. } => Some(xuid),
+            _ => None,
+        }
+    }
+
+    pub fn client_id(&self) -> Option<&str> {
+        match self {
+            Account::Microsoft { client_id, .. } => client_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// whether the access token is known to be expired without asking the server
+    pub fn is_expired(&self) -> bool {
+        match self {
+            Account::Microsoft {
+                expires_at: Some(expires_at),
+                ..
+            } => *expires_at <= chrono::Local::now().timestamp_millis(),
+            _ => false,
+        }
+    }
+
+    /// make the token unusable on the server, offline and microsoft accounts have nothing to do
+    pub async fn invalidate(&self) -> Result<(), YggdrasilError> {
+        let client = match self {
+            Account::Yggdrasil { .. } => YggdrasilClient::new(MOJANG_AUTH_SERVER),
+            Account::AuthlibInjector { api_location, .. } => {
+                YggdrasilClient::authlib_injector(api_location)
+            }
+            _ => return Ok(()),
+        };
+        if let Account::Yggdrasil {
+            access_token,
+            client_token,
+            ..
+        }
+        | Account::AuthlibInjector {
+            access_token,
+            client_token,
+            ..
+        } = self
+        {
+            client
+                .invalidate(access_token, client_token.as_deref())
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// check the access token and refresh it if it is stale.
+    /// returns the refreshed account, or none if the token is still valid
+    pub async fn refresh_if_needed(
+        &self,
+        microsoft_config: &MicrosoftAuthConfig,
+    ) -> Result<Option<Account>, AccountRefreshError> {
+        match self {
+            Account::Offline { .. } => Ok(None),
+            Account::Microsoft {
+                refresh_token,
+                client_id,
+                expires_at,
+                ..
+            } => {
+                let now = chrono::Local::now().timestamp_millis();
+                if expires_at.is_some_and(|expires_at| expires_at > now + EXPIRY_MARGIN_MS) {
+                    return Ok(None);
+                }
+                log::info!("refreshing microsoft account {}", self.username());
+                let mut config = microsoft_config.clone();
+                // the refresh token only works with the client it was issued to
+                if let Some(client_id) = client_id {
+                    config.client_id = Some(client_id.clone());
+                }
+                match MicrosoftAuth::new(config).refresh(refresh_token).await {
+                    Ok(account) => Ok(Some(account)),
+                    Err(MicrosoftAuthError::OAuthError { error, description })
+                        if error == "invalid_grant" =>
+                    {
+                        Err(AccountRefreshError::ReloginRequired(description))
+                    }
+                    Err(err) => Err(err.into()),
+                }
+            }
+            Account::Yggdrasil {
+                access_token,
+                client_token,
+                ..
+            }
+            | Account::AuthlibInjector {
+                access_token,
+                client_token,
+                ..
+            } => {
+                let client = match self {
+                    Account::AuthlibInjector { api_location, .. } => {
+                        YggdrasilClient::authlib_injector(api_location)
+                    }
+                    _ => YggdrasilClient::new(MOJANG_AUTH_SERVER),
+                };
+                if client
+                    .validate(access_token, client_token.as_deref())
+                    .await?
+                {
+                    return Ok(None);
+                }
+                log::info!("refreshing yggdrasil account {}", self.username());
+                let refreshed = match client
+                    .refresh(access_token, client_token.as_deref(), None)
+                    .await
+                {
+                    Ok(refreshed) => refreshed,
+                    Err(YggdrasilError::Forbidden(message)) => {
+                        return Err(AccountRefreshError::ReloginRequired(message));
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                let mut account = self.clone();
+                if let Account::Yggdrasil {
+                    username,
+                    uuid,
+                    access_token,
+                    client_token,
+                    user_properties,
+                }
+                | Account::AuthlibInjector {
+                    username,
+                    uuid,
+                    access_token,
+                    client_token,
+                    user_properties,
+                    ..
+                } = &mut account
+                {
+                    *access_token = refreshed.access_token;
+                    *client_token = Some(refreshed.client_token);
+                    if let Some(user) = refreshed.user {
+                        *user_properties = user.property_map();
+                    }
+                    // the profile may have been renamed since the last login
+                    if let Some(profile) = refreshed.selected_profile {
+                        *username = profile.name;
+                        *uuid = profile.id;
+                    }
+                }
+                Ok(Some(account))
+            }
+        }
+    }
+}
+
+/// add the account, or replace the same one logged in before. returns its index
+pub fn upsert_account(accounts: &mut Vec<Account>, account: Account) -> usize {
+    match accounts.iter().position(|item| item.is_same(&account)) {
+        Some(index) => {
+            accounts[index] = account;
+            index
+        }
+        None => {
+            accounts.push(account);
+            accounts.len() - 1
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AccountRefreshError {
+    /// the refresh token or the session is gone, the user has to log in again
+    #[error("re-login required: {0}")]
+    ReloginRequired(String),
+
+    #[error(transparent)]
+    MicrosoftError(#[from] MicrosoftAuthError),
+
+    #[error(transparent)]
+    YggdrasilError(#[from] YggdrasilError),
+}
+
+#[cfg(test)]
+#[test]
+fn upsert_account_test() {
+    let offline = |username: &str, uuid: &str| Account::Offline {
+        username: username.to_string(),
+        uuid: uuid.to_string(),
+    };
+    let mut accounts = vec![offline("Steve", "0123"), offline("Alex", "4567")];
+    assert_eq!(upsert_account(&mut accounts, offline("Steve2", "0123")), 0);
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0].username(), "Steve2");
+    let yggdrasil = Account::Yggdrasil {
+        username: "Steve".to_string(),
+        uuid: "0123".to_string(),
+        access_token: "token".to_string(),
+        client_token: None,
+        user_properties: Default::default(),
+    };
+    assert_eq!(upsert_account(&mut accounts, yggdrasil), 2);
+}
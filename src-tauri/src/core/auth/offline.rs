@@ -0,0 +1,78 @@
+//! Offline accounts, whose uuid is derived from the username the same way as vanilla,
+//! so the player keeps the same data in every launcher.
+use md5::{Digest, Md5};
+use uuid::{Builder, Uuid};
+
+use crate::core::auth::Account;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OfflineAccountError {
+    #[error("username must be 3 to 16 characters long")]
+    UsernameLength,
+
+    #[error("username may only contain letters, digits and underscores")]
+    UsernameCharacter,
+
+    #[error("invalid uuid: {0}")]
+    MalformedUuid(String),
+}
+
+/// `UUID.nameUUIDFromBytes(("OfflinePlayer:" + name).getBytes(UTF_8))` of the server,
+/// a version 3 uuid of the md5 without a namespace
+pub fn offline_uuid(username: &str) -> String {
+    let digest = Md5::digest(format!("OfflinePlayer:{}", username).as_bytes());
+    Builder::from_md5_bytes(digest.into())
+        .into_uuid()
+        .simple()
+        .to_string()
+}
+
+/// usernames the game accepts, which are also safe in paths and arguments
+pub fn validate_username(username: &str) -> Result<(), OfflineAccountError> {
+    if !(3..=16).contains(&username.len()) {
+        return Err(OfflineAccountError::UsernameLength);
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(OfflineAccountError::UsernameCharacter);
+    }
+    Ok(())
+}
+
+impl Account {
+    /// an offline account, the uuid may be given to keep the data of a player from elsewhere
+    pub fn offline(username: &str, uuid: Option<&str>) -> Result<Account, OfflineAccountError> {
+        validate_username(username)?;
+        let uuid = match uuid.map(str::trim).filter(|uuid| !uuid.is_empty()) {
+            Some(uuid) => Uuid::try_parse(uuid)
+                .map_err(|_| OfflineAccountError::MalformedUuid(uuid.to_string()))?
+                .simple()
+                .to_string(),
+            None => offline_uuid(username),
+        };
+        Ok(Account::Offline {
+            username: username.to_string(),
+            uuid,
+        })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn offline_account_test() {
+    assert_eq!(offline_uuid("Notch"), "b50ad385829d3141a2167e7d7539ba7f");
+    let account =
+        Account::offline("Steve_01", Some("31BBE537-9FEA-4E68-AA4A-D7AACCA23D13")).unwrap();
+    assert_eq!(account.uuid(), "31bbe5379fea4e68aa4ad7aacca23d13");
+    assert!(matches!(
+        Account::offline("St", None),
+        Err(OfflineAccountError::UsernameLength)
+    ));
+    assert!(matches!(
+        Account::offline("Steve Alex", None),
+        Err(OfflineAccountError::UsernameCharacter)
+    ));
+    assert!(Account::offline("Steve", Some("not-a-uuid")).is_err());
+}
@@ -0,0 +1,450 @@
+//! Client of the yggdrasil authentication server,
+//! used by legacy mojang accounts and third-party servers of authlib-injector.
+//! See [Yggdrasil 服务端技术规范](https://github.com/yushijinhun/authlib-injector/wiki/Yggdrasil-服务端技术规范)
+use std::collections::HashMap;
+
+use reqwest::{Client, StatusCode, Url};
+use serde_json::json;
+
+use crate::{core::auth::Account, setup::constants::USER_AGENT};
+
+/// the auth server of legacy mojang accounts
+pub const MOJANG_AUTH_SERVER: &str = "https://authserver.mojang.com";
+/// API Location Indication, the header pointing to the api of a website
+const API_LOCATION_HEADER: &str = "X-Authlib-Injector-API-Location";
+
+#[derive(Debug, thiserror::Error)]
+pub enum YggdrasilError {
+    #[error("HTTP request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    /// the token is invalid or the credentials are wrong
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{error}: {message}")]
+    ServerError { error: String, message: String },
+
+    #[error("unexpected response: {0}")]
+    InvalidResponse(String),
+
+    #[error("invalid server url: {0}")]
+    InvalidUrl(String),
+
+    #[error("the account has no game profile")]
+    NoProfile,
+
+    #[error("profile {0} not found")]
+    ProfileNotFound(String),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_message: String,
+}
+
+/// the client token is optional, and left out rather than null if not given
+fn token_body(access_token: &str, client_token: Option<&str>) -> serde_json::Value {
+    let mut body = json!({ "accessToken": access_token });
+    if let Some(client_token) = client_token {
+        body["clientToken"] = json!(client_token);
+    }
+    body
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GameProfile {
+    pub id: String,
+    pub name: String,
+}
+
+/// response of `/authenticate` and `/refresh`, only `/authenticate` has the available profiles
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub client_token: String,
+    #[serde(default)]
+    pub available_profiles: Vec<GameProfile>,
+    pub selected_profile: Option<GameProfile>,
+    /// given as `requestUser` is set
+    pub user: Option<User>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct User {
+    #[serde(default)]
+    pub properties: Vec<UserProperty>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct UserProperty {
+    pub name: String,
+    pub value: String,
+}
+
+impl User {
+    /// the properties by name, as `${user_properties}` of legacy versions takes
+    pub fn property_map(&self) -> HashMap<String, Vec<String>> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for property in &self.properties {
+            map.entry(property.name.clone())
+                .or_default()
+                .push(property.value.clone());
+        }
+        map
+    }
+}
+
+/// a yggdrasil server of authlib-injector and its metadata
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AuthlibInjectorServer {
+    pub api_location: String,
+    pub server_name: String,
+    pub server_homepage: String,
+    /// the raw response of the api root, passed to authlib-injector when launching
+    pub server_meta: String,
+}
+
+#[derive(serde::Deserialize)]
+struct ServerMeta {
+    #[serde(default)]
+    meta: ServerMetaInfo,
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerMetaInfo {
+    #[serde(default)]
+    server_name: String,
+    #[serde(default)]
+    links: ServerLinks,
+}
+
+#[derive(Default, serde::Deserialize)]
+struct ServerLinks {
+    #[serde(default)]
+    homepage: String,
+}
+
+impl AuthlibInjectorServer {
+    /// find the api from the url the user entered, which may be the website of the server.
+    /// the url is followed once if it indicates another api location
+    pub async fn discover(url: &str) -> Result<Self, YggdrasilError> {
+        let url = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("https://{}", url)
+        };
+        let url = Url::parse(&url).map_err(|_| YggdrasilError::InvalidUrl(url))?;
+        let client = Client::builder().user_agent(USER_AGENT).build().unwrap();
+        let mut response = client.get(url.clone()).send().await?;
+        if let Some(location) = api_location_of(&response)
+            && location != *response.url()
+        {
+            log::info!("api location of {} indicated as {}", url, location);
+            response = client.get(location).send().await?;
+        }
+        let api_location = response.url().as_str().trim_end_matches('/').to_string();
+        let server_meta = response.error_for_status()?.text().await?;
+        let meta: ServerMeta = serde_json::from_str(&server_meta)
+            .map_err(|err| YggdrasilError::InvalidResponse(err.to_string()))?;
+        Ok(Self {
+            api_location,
+            server_name: meta.meta.server_name,
+            server_homepage: meta.meta.links.homepage,
+            server_meta,
+        })
+    }
+}
+
+/// the header is relative to the url responding it
+fn api_location_of(response: &reqwest::Response) -> Option<Url> {
+    let location = response.headers().get(API_LOCATION_HEADER)?.to_str().ok()?;
+    response.url().join(location).ok()
+}
+
+/// the result of logging in with a password
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginResult {
+    LoggedIn {
+        account: Account,
+    },
+    /// the account has several profiles and none is selected, one should be chosen
+    /// with `select_profile`
+    SelectProfile(PendingLogin),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PendingLogin {
+    /// none for the auth server of mojang
+    pub server: Option<AuthlibInjectorServer>,
+    pub access_token: String,
+    pub client_token: String,
+    pub profiles: Vec<GameProfile>,
+}
+
+pub struct YggdrasilClient {
+    client: Client,
+    /// the url of the auth server, such as `https://authserver.mojang.com`
+    /// or `<api location>/authserver` of authlib-injector
+    auth_server: String,
+}
+
+impl YggdrasilClient {
+    pub fn new(auth_server: impl Into<String>) -> Self {
+        Self {
+            client: Client::builder().user_agent(USER_AGENT).build().unwrap(),
+            auth_server: auth_server.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// the client of a server of authlib-injector, from its api location
+    pub fn authlib_injector(api_location: &str) -> Self {
+        Self::new(format!("{}/authserver", api_location.trim_end_matches('/')))
+    }
+
+    /// the server of mojang if none is given
+    pub fn of_server(server: Option<&AuthlibInjectorServer>) -> Self {
+        match server {
+            Some(server) => Self::authlib_injector(&server.api_location),
+            None => Self::new(MOJANG_AUTH_SERVER),
+        }
+    }
+
+    async fn post(
+        &self,
+        path: &str,
+        body: serde_json::Value,
+    ) -> Result<reqwest::Response, YggdrasilError> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.auth_server, path))
+            .json(&body)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let text = response.text().await?;
+        let error: ErrorResponse = serde_json::from_str(&text)
+            .map_err(|_| YggdrasilError::InvalidResponse(format!("{}: {}", status, text)))?;
+        if status == StatusCode::FORBIDDEN {
+            Err(YggdrasilError::Forbidden(error.error_message))
+        } else {
+            Err(YggdrasilError::ServerError {
+                error: error.error,
+                message: error.error_message,
+            })
+        }
+    }
+
+    /// whether the access token is still usable
+    pub async fn validate(
+        &self,
+        access_token: &str,
+        client_token: Option<&str>,
+    ) -> Result<bool, YggdrasilError> {
+        match self
+            .post("/validate", token_body(access_token, client_token))
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(YggdrasilError::Forbidden(_)) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// log in with the username, usually the email, and the password.
+    /// the server generates the client token
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<TokenResponse, YggdrasilError> {
+        let response = self
+            .post(
+                "/authenticate",
+                json!({
+                    "agent": { "name": "Minecraft", "version": 1 },
+                    "username": username,
+                    "password": password,
+                    "requestUser": true,
+                }),
+            )
+            .await?;
+        parse_json(response).await
+    }
+
+    /// a new access token in place of the old one, which becomes invalid.
+    /// the profile is bound to the new token if selected
+    pub async fn refresh(
+        &self,
+        access_token: &str,
+        client_token: Option<&str>,
+        selected_profile: Option<&GameProfile>,
+    ) -> Result<TokenResponse, YggdrasilError> {
+        let mut body = token_body(access_token, client_token);
+        body["requestUser"] = json!(true);
+        if let Some(profile) = selected_profile {
+            body["selectedProfile"] = json!(profile);
+        }
+        let response = self.post("/refresh", body).await?;
+        parse_json(response).await
+    }
+
+    pub async fn invalidate(
+        &self,
+        access_token: &str,
+        client_token: Option<&str>,
+    ) -> Result<(), YggdrasilError> {
+        self.post("/invalidate", token_body(access_token, client_token))
+            .await?;
+        Ok(())
+    }
+
+    /// invalidate all the tokens of the user
+    pub async fn signout(&self, username: &str, password: &str) -> Result<(), YggdrasilError> {
+        self.post(
+            "/signout",
+            json!({ "username": username, "password": password }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+async fn parse_json<T: serde::de::DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<T, YggdrasilError> {
+    let text = response.text().await?;
+    serde_json::from_str(&text).map_err(|err| YggdrasilError::InvalidResponse(err.to_string()))
+}
+
+/// log in to the server, or the server of mojang if none is given
+pub async fn login(
+    server: Option<AuthlibInjectorServer>,
+    username: &str,
+    password: &str,
+) -> Result<LoginResult, YggdrasilError> {
+    let client = YggdrasilClient::of_server(server.as_ref());
+    let token = client.authenticate(username, password).await?;
+    if let Some(profile) = token.selected_profile.clone() {
+        return Ok(LoginResult::LoggedIn {
+            account: make_account(server, token, profile),
+        });
+    }
+    match token.available_profiles.as_slice() {
+        [] => Err(YggdrasilError::NoProfile),
+        [profile] => {
+            let profile = profile.clone();
+            let token = client
+                .refresh(
+                    &token.access_token,
+                    Some(&token.client_token),
+                    Some(&profile),
+                )
+                .await?;
+            Ok(LoginResult::LoggedIn {
+                account: make_account(server, token, profile),
+            })
+        }
+        _ => Ok(LoginResult::SelectProfile(PendingLogin {
+            server,
+            access_token: token.access_token,
+            client_token: token.client_token,
+            profiles: token.available_profiles,
+        })),
+    }
+}
+
+/// finish a login by binding one of its profiles to the token
+pub async fn select_profile(
+    pending: PendingLogin,
+    profile_id: &str,
+) -> Result<Account, YggdrasilError> {
+    let profile = pending
+        .profiles
+        .iter()
+        .find(|profile| profile.id == profile_id)
+        .cloned()
+        .ok_or_else(|| YggdrasilError::ProfileNotFound(profile_id.to_string()))?;
+    let token = YggdrasilClient::of_server(pending.server.as_ref())
+        .refresh(
+            &pending.access_token,
+            Some(&pending.client_token),
+            Some(&profile),
+        )
+        .await?;
+    Ok(make_account(pending.server, token, profile))
+}
+
+fn make_account(
+    server: Option<AuthlibInjectorServer>,
+    token: TokenResponse,
+    profile: GameProfile,
+) -> Account {
+    let user_properties = token
+        .user
+        .as_ref()
+        .map(User::property_map)
+        .unwrap_or_default();
+    match server {
+        Some(server) => Account::AuthlibInjector {
+            api_location: server.api_location,
+            server_name: server.server_name,
+            server_homepage: server.server_homepage,
+            server_meta: server.server_meta,
+            access_token: token.access_token,
+            client_token: Some(token.client_token),
+            uuid: profile.id,
+            username: profile.name,
+            user_properties,
+        },
+        None => Account::Yggdrasil {
+            username: profile.name,
+            uuid: profile.id,
+            access_token: token.access_token,
+            client_token: Some(token.client_token),
+            user_properties,
+        },
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn yggdrasil_responses() {
+    let token: TokenResponse = serde_json::from_str(
+        r#"{"accessToken":"a","clientToken":"c","availableProfiles":[{"id":"0123","name":"Steve"}]}"#,
+    )
+    .unwrap();
+    assert!(token.selected_profile.is_none());
+    let account = make_account(None, token.clone(), token.available_profiles[0].clone());
+    assert_eq!(account.username(), "Steve");
+    assert_eq!(account.access_token(), Some("a"));
+    assert_eq!(account.user_properties(), "{}");
+
+    let token: TokenResponse = serde_json::from_str(
+        r#"{"accessToken":"a","clientToken":"c","selectedProfile":{"id":"0123","name":"Steve"},
+        "user":{"id":"4567","properties":[{"name":"preferredLanguage","value":"zh_CN"}]}}"#,
+    )
+    .unwrap();
+    let profile = token.selected_profile.clone().unwrap();
+    let account = make_account(None, token, profile);
+    assert_eq!(
+        account.user_properties(),
+        r#"{"preferredLanguage":["zh_CN"]}"#
+    );
+
+    let meta: ServerMeta = serde_json::from_str(
+        r#"{"meta":{"serverName":"LittleSkin","links":{"homepage":"https://littleskin.cn"}},"skinDomains":[]}"#,
+    )
+    .unwrap();
+    assert_eq!(meta.meta.server_name, "LittleSkin");
+    assert_eq!(meta.meta.links.homepage, "https://littleskin.cn");
+}
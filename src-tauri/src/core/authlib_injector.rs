@@ -0,0 +1,151 @@
+//! authlib-injector, the java agent which redirects the authentication of the game
+//! to a third-party yggdrasil server.
+//! See [authlib-injector](https://github.com/yushijinhun/authlib-injector/wiki) for the arguments
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    core::api_client::McApiError,
+    setup::{ConfigManager, constants::USER_AGENT},
+};
+
+const JAR_NAME: &str = "authlib-injector.jar";
+/// the artifact info of the cached jar, kept to verify the jar before every launch
+const INFO_NAME: &str = "authlib-injector.json";
+
+/// `artifact/latest.json` of the authlib-injector api
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactInfo {
+    pub build_number: u32,
+    pub version: String,
+    pub download_url: String,
+    pub checksums: ArtifactChecksums,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactChecksums {
+    pub sha256: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthlibInjectorError {
+    #[error("failed to fetch authlib-injector: {0}")]
+    ApiError(#[from] McApiError),
+
+    #[error("failed to download authlib-injector: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("sha256 of authlib-injector {version} mismatched")]
+    ChecksumMismatch { version: String },
+}
+
+/// where the jar is cached, shared by all the instances
+pub fn jar_path() -> PathBuf {
+    ConfigManager::instance()
+        .authlib_injector_dir()
+        .join(JAR_NAME)
+}
+
+/// make sure the cached jar is there and not corrupted, the latest one is downloaded otherwise
+pub async fn ensure_jar() -> Result<PathBuf, AuthlibInjectorError> {
+    let jar_path = jar_path();
+    let info_path = jar_path.with_file_name(INFO_NAME);
+    if let Some(info) = read_cached_info(&info_path).await
+        && verify(&jar_path, &info.checksums.sha256).await
+    {
+        return Ok(jar_path);
+    }
+
+    let api_client = &ConfigManager::instance().api_client;
+    let authlib_injector_base = api_client.api_bases_async().await.authlib_injector_base;
+    let info: ArtifactInfo = api_client
+        .get(
+            &format!("{}/artifact/latest.json", authlib_injector_base),
+            false,
+        )
+        .await?;
+    log::info!("downloading authlib-injector {}", info.version);
+    let bytes = reqwest::Client::new()
+        .get(&info.download_url)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    if !sha256_matches(&bytes, &info.checksums.sha256) {
+        return Err(AuthlibInjectorError::ChecksumMismatch {
+            version: info.version,
+        });
+    }
+    if let Some(parent) = jar_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&jar_path, &bytes).await?;
+    tokio::fs::write(
+        &info_path,
+        serde_json::to_vec(&info).map_err(McApiError::from)?,
+    )
+    .await?;
+    Ok(jar_path)
+}
+
+async fn read_cached_info(info_path: &Path) -> Option<ArtifactInfo> {
+    let content = tokio::fs::read(info_path).await.ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+async fn verify(jar_path: &Path, sha256: &str) -> bool {
+    match tokio::fs::read(jar_path).await {
+        Ok(bytes) => sha256_matches(&bytes, sha256),
+        Err(_) => false,
+    }
+}
+
+fn sha256_matches(bytes: &[u8], sha256: &str) -> bool {
+    let digest = Sha256::digest(bytes);
+    let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+    hex.eq_ignore_ascii_case(sha256)
+}
+
+/// jvm arguments to load the agent, the server meta is prefetched so the agent needn't request it again
+pub fn jvm_arguments(jar_path: &Path, api_location: &str, server_meta: &str) -> Vec<String> {
+    let mut args = vec![format!(
+        "-javaagent:{}={}",
+        jar_path.display(),
+        api_location
+    )];
+    if !server_meta.is_empty() {
+        args.push(format!(
+            "-Dauthlibinjector.yggdrasil.prefetched={}",
+            base64::engine::general_purpose::STANDARD.encode(server_meta)
+        ));
+    }
+    args
+}
+
+#[cfg(test)]
+#[test]
+fn authlib_injector_jvm_arguments() {
+    let args = jvm_arguments(
+        Path::new("/pcl/authlib-injector.jar"),
+        "https://littleskin.cn/api/yggdrasil",
+        r#"{"meta":{}}"#,
+    );
+    assert_eq!(
+        args,
+        [
+            "-javaagent:/pcl/authlib-injector.jar=https://littleskin.cn/api/yggdrasil",
+            "-Dauthlibinjector.yggdrasil.prefetched=eyJtZXRhIjp7fX0="
+        ]
+    );
+    assert!(sha256_matches(
+        b"abc",
+        "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD"
+    ));
+}
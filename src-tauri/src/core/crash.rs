@@ -0,0 +1,399 @@
+//! Crash analysis for exited game processes.
+//! Inspired by the crash analysis of PCL-Community/PCL-CE,
+//! the output of the game, crash reports, JVM error logs and latest.log are
+//! matched against known patterns to find out why the game crashed.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::SystemTime,
+};
+
+use regex::Regex;
+
+/// a known cause of crash
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CrashCause {
+    /// `required_major` is known when the java is too old,
+    /// and `too_new` is set when the java is too new for the game or modloader
+    WrongJavaVersion {
+        required_major: Option<u32>,
+        too_new: bool,
+    },
+    OutOfMemory,
+    MissingModDependency {
+        mod_id: Option<String>,
+        dependency: String,
+    },
+    DuplicatedMods {
+        mod_id: Option<String>,
+    },
+    MixinFailure {
+        mod_id: Option<String>,
+    },
+    OpenGLDriver,
+    CorruptedJar {
+        path: Option<String>,
+    },
+}
+
+impl CrashCause {
+    /// whether the cause is found without knowing which mod or file is to blame
+    fn is_vague(&self) -> bool {
+        matches!(
+            self,
+            CrashCause::DuplicatedMods { mod_id: None }
+                | CrashCause::MixinFailure { mod_id: None }
+                | CrashCause::CorruptedJar { path: None }
+        )
+    }
+
+    pub fn suggestion(&self) -> String {
+        match self {
+            CrashCause::WrongJavaVersion {
+                required_major: Some(major),
+                ..
+            } => format!("The game requires Java {major} or newer, please switch the Java."),
+            CrashCause::WrongJavaVersion { too_new: true, .. } => {
+                "The Java is too new for this version or modloader, please use an older Java, such as Java 8 for Forge before 1.17.".to_string()
+            }
+            CrashCause::WrongJavaVersion { .. } => {
+                "The Java does not match this version, please switch the Java.".to_string()
+            }
+            CrashCause::OutOfMemory => {
+                "The game ran out of memory, please allocate more memory or remove some mods.".to_string()
+            }
+            CrashCause::MissingModDependency {
+                mod_id, dependency, ..
+            } => match mod_id {
+                Some(mod_id) => format!("Mod {mod_id} requires {dependency}, please install it."),
+                None => format!("A mod requires {dependency}, please install it."),
+            },
+            CrashCause::DuplicatedMods { mod_id } => match mod_id {
+                Some(mod_id) => format!("Mod {mod_id} is installed more than once, please keep only one of them."),
+                None => "Some mods are installed more than once, please keep only one of them.".to_string(),
+            },
+            CrashCause::MixinFailure { mod_id } => match mod_id {
+                Some(mod_id) => format!("Mod {mod_id} failed to inject, it may be incompatible with the game or other mods, please update or remove it."),
+                None => "A mod failed to inject, it may be incompatible with the game or other mods, please check the recently added mods.".to_string(),
+            },
+            CrashCause::OpenGLDriver => {
+                "The graphics driver does not support the game, please update the graphics driver, or make sure the game runs on the dedicated GPU.".to_string()
+            }
+            CrashCause::CorruptedJar { path } => match path {
+                Some(path) => format!("{path} is corrupted, please delete it and download again."),
+                None => "A jar file is corrupted, please complete the game files and check the mods.".to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CrashCauseReport {
+    pub cause: CrashCause,
+    /// the line of log proving the cause
+    pub evidence: String,
+    pub suggestion: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CrashAnalysis {
+    pub causes: Vec<CrashCauseReport>,
+    pub crash_report: Option<PathBuf>,
+    pub jvm_error_log: Option<PathBuf>,
+    pub latest_log: Option<PathBuf>,
+}
+
+/// a pattern matched against log text, producing a cause from the captures
+struct CrashPattern {
+    regex: Regex,
+    to_cause: fn(&regex::Captures) -> CrashCause,
+}
+
+fn capture(captures: &regex::Captures, name: &str) -> Option<String> {
+    captures.name(name).map(|m| m.as_str().trim().to_string())
+}
+
+static PATTERNS: LazyLock<Vec<CrashPattern>> = LazyLock::new(|| {
+    let pattern = |regex: &str, to_cause: fn(&regex::Captures) -> CrashCause| CrashPattern {
+        regex: Regex::new(regex).unwrap(),
+        to_cause,
+    };
+    vec![
+        // java too old: class file version 65.0 -> java 21
+        pattern(
+            r"class file version (?P<class>\d+)\.0\), this version of the Java Runtime only recognizes class file versions up to",
+            |captures| CrashCause::WrongJavaVersion {
+                required_major: capture(captures, "class")
+                    .and_then(|class| class.parse::<u32>().ok())
+                    .and_then(|class| class.checked_sub(44)),
+                too_new: false,
+            },
+        ),
+        pattern(
+            r"(Unsupported class file major version \d+|AppClassLoader cannot be cast to (class )?java\.net\.URLClassLoader)",
+            |_| CrashCause::WrongJavaVersion {
+                required_major: None,
+                too_new: true,
+            },
+        ),
+        pattern(
+            r"(java\.lang\.OutOfMemoryError|Could not reserve enough space for|There is insufficient memory for the Java Runtime Environment)",
+            |_| CrashCause::OutOfMemory,
+        ),
+        // fabric
+        pattern(
+            r"Mod '[^']+' \((?P<id>[^)]+)\) \S+ requires .*? of (?P<dep>[^\s,]+),? which is missing",
+            |captures| CrashCause::MissingModDependency {
+                mod_id: capture(captures, "id"),
+                dependency: capture(captures, "dep").unwrap_or_default(),
+            },
+        ),
+        // forge and neoforge
+        pattern(
+            r"Mod ID: '(?P<dep>[^']+)', Requested by: '(?P<id>[^']+)'",
+            |captures| CrashCause::MissingModDependency {
+                mod_id: capture(captures, "id"),
+                dependency: capture(captures, "dep").unwrap_or_default(),
+            },
+        ),
+        pattern(r"Mod ID: '(?P<id>[^']+)' from mod files:", |captures| {
+            CrashCause::DuplicatedMods {
+                mod_id: capture(captures, "id"),
+            }
+        }),
+        pattern(
+            r"(DuplicateModsFoundException|Found a duplicate mod|ModResolutionException: Duplicate)",
+            |_| CrashCause::DuplicatedMods { mod_id: None },
+        ),
+        pattern(
+            r"Mixin (apply|prepare) for mod (?P<id>[\w-]+) failed",
+            |captures| CrashCause::MixinFailure {
+                mod_id: capture(captures, "id"),
+            },
+        ),
+        pattern(
+            r"(?P<id>[\w-]+)\.mixins\.json.*(failed|FAILED)",
+            |captures| CrashCause::MixinFailure {
+                mod_id: capture(captures, "id"),
+            },
+        ),
+        pattern(
+            r"(MixinApplyError|MixinTransformerError|Mixin (apply|prepare|transformation) .*failed)",
+            |_| CrashCause::MixinFailure { mod_id: None },
+        ),
+        pattern(
+            r"(Pixel format not accelerated|GLFW error 65542|The driver does not appear to support OpenGL|Couldn't set pixel format|No OpenGL context found|(atio6axx|atioglxx|nvoglv(32|64)|ig\d+icd(32|64))\.dll)",
+            |_| CrashCause::OpenGLDriver,
+        ),
+        pattern(
+            r"(Invalid or corrupt jarfile (?P<path>\S+)|java\.util\.zip\.ZipException|error in opening zip file|zip END header not found)",
+            |captures| CrashCause::CorruptedJar {
+                path: capture(captures, "path"),
+            },
+        ),
+    ]
+});
+
+/// match the text line by line against all the known patterns
+pub fn analyze_text(text: &str) -> Vec<CrashCauseReport> {
+    let mut reports: Vec<CrashCauseReport> = Vec::new();
+    for line in text.lines() {
+        for pattern in PATTERNS.iter() {
+            if let Some(captures) = pattern.regex.captures(line) {
+                let cause = (pattern.to_cause)(&captures);
+                if reports.iter().any(|report| report.cause == cause) {
+                    continue;
+                }
+                reports.push(CrashCauseReport {
+                    suggestion: cause.suggestion(),
+                    cause,
+                    evidence: line.trim().to_string(),
+                });
+            }
+        }
+    }
+    // drop the vague causes if the same kind of cause is found in detail
+    let detailed: Vec<_> = reports
+        .iter()
+        .filter(|report| !report.cause.is_vague())
+        .map(|report| std::mem::discriminant(&report.cause))
+        .collect();
+    reports.retain(|report| {
+        !report.cause.is_vague() || !detailed.contains(&std::mem::discriminant(&report.cause))
+    });
+    reports
+}
+
+/// the newest file in the folder matching the filter, modified after `since` if given
+fn newest_file(
+    folder: &Path,
+    filter: impl Fn(&str) -> bool,
+    since: Option<SystemTime>,
+) -> Option<PathBuf> {
+    fs::read_dir(folder)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| filter(&entry.file_name().to_string_lossy()))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .filter(|(modified, _)| since.is_none_or(|since| *modified >= since))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+/// analyze the crash of a game with its captured output and the files in its game directory.
+/// files modified before `since` are ignored, as they belong to the previous crashes
+pub fn analyze(output: &[String], game_dir: &Path, since: Option<SystemTime>) -> CrashAnalysis {
+    let mut analysis = CrashAnalysis {
+        crash_report: newest_file(
+            &game_dir.join("crash-reports"),
+            |name| name.ends_with(".txt"),
+            since,
+        ),
+        jvm_error_log: newest_file(
+            game_dir,
+            |name| name.starts_with("hs_err_pid") && name.ends_with(".log"),
+            since,
+        ),
+        latest_log: newest_file(&game_dir.join("logs"), |name| name == "latest.log", since),
+        ..Default::default()
+    };
+    let mut text = output.join("\n");
+    for path in [
+        &analysis.crash_report,
+        &analysis.jvm_error_log,
+        &analysis.latest_log,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        match fs::read(path) {
+            Ok(bytes) => {
+                text.push('\n');
+                text.push_str(&String::from_utf8_lossy(&bytes));
+            }
+            Err(e) => log::warn!("failed to read {:?} for crash analysis: {}", path, e),
+        }
+    }
+    analysis.causes = analyze_text(&text);
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/crash")
+            .join(name);
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn causes_of(name: &str) -> Vec<CrashCause> {
+        analyze_text(&fixture(name))
+            .into_iter()
+            .map(|report| report.cause)
+            .collect()
+    }
+
+    #[test]
+    fn wrong_java_version() {
+        assert_eq!(
+            causes_of("wrong_java.log"),
+            [CrashCause::WrongJavaVersion {
+                required_major: Some(21),
+                too_new: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn out_of_memory() {
+        assert_eq!(causes_of("hs_err_pid1234.log"), [CrashCause::OutOfMemory]);
+    }
+
+    #[test]
+    fn fabric_missing_dependency() {
+        assert_eq!(
+            causes_of("fabric_missing_dependency.log"),
+            [CrashCause::MissingModDependency {
+                mod_id: Some("sodium-extra".to_string()),
+                dependency: "sodium".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn forge_duplicated_mods() {
+        assert_eq!(
+            causes_of("forge_duplicated_mods.log"),
+            [CrashCause::DuplicatedMods {
+                mod_id: Some("jei".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn mixin_failure() {
+        assert_eq!(
+            causes_of("mixin_failure.txt"),
+            [CrashCause::MixinFailure {
+                mod_id: Some("examplemod".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn opengl_and_corrupted_jar() {
+        assert_eq!(
+            causes_of("opengl_corrupted_jar.log"),
+            [
+                CrashCause::OpenGLDriver,
+                CrashCause::CorruptedJar {
+                    path: Some("/home/steve/.minecraft/versions/1.21.8/1.21.8.jar".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn analyze_game_dir() {
+        let game_dir = std::env::temp_dir().join("pcl-proto-crash-test");
+        let _ = fs::remove_dir_all(&game_dir);
+        fs::create_dir_all(game_dir.join("crash-reports")).unwrap();
+        fs::write(
+            game_dir.join("crash-reports/crash-2025-01-01_00.00.00-client.txt"),
+            fixture("mixin_failure.txt"),
+        )
+        .unwrap();
+        fs::create_dir_all(game_dir.join("logs")).unwrap();
+        fs::write(game_dir.join("logs/latest.log"), "").unwrap();
+        let output = vec!["java.lang.OutOfMemoryError: Java heap space".to_string()];
+        let analysis = analyze(&output, &game_dir, None);
+        assert!(analysis.crash_report.is_some());
+        assert!(analysis.jvm_error_log.is_none());
+        assert!(analysis.latest_log.is_some());
+        let causes: Vec<_> = analysis
+            .causes
+            .into_iter()
+            .map(|report| report.cause)
+            .collect();
+        assert_eq!(
+            causes,
+            [
+                CrashCause::OutOfMemory,
+                CrashCause::MixinFailure {
+                    mod_id: Some("examplemod".to_string()),
+                },
+            ]
+        );
+        // the files are left by a previous run
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        let analysis = analyze(&output, &game_dir, Some(later));
+        assert!(analysis.crash_report.is_none());
+        assert!(analysis.latest_log.is_none());
+        fs::remove_dir_all(&game_dir).unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+//! Limits of the downloads: the bandwidth and the connections to a host are shared by all
+//! the downloaders, so several tasks together don't saturate the network.
+//! The files downloaded at once by a task may adapt to the throughput and the errors.
+use std::{
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+
+/// the limits shared by every downloader
+pub static SHARED_LIMITS: LazyLock<Arc<DownloadLimits>> =
+    LazyLock::new(|| Arc::new(DownloadLimits::default()));
+
+/// how often the adaptive concurrency looks at the throughput
+const ADAPT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// the download settings in the config
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DownloadSettings {
+    /// bytes per second of all the downloads together, 0 for no limit
+    pub speed_limit: u64,
+    /// the most files downloaded at once by a task
+    pub max_concurrency: usize,
+    /// the most files downloaded from the same host at once, by all the tasks
+    pub max_per_host: usize,
+    /// start with fewer files at once and adjust to the throughput and the errors,
+    /// up to `max_concurrency`
+    pub adaptive: bool,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            speed_limit: 0,
+            max_concurrency: 8,
+            max_per_host: 8,
+            adaptive: false,
+        }
+    }
+}
+
+struct TokenBucket {
+    /// bytes which can be received right now, negative when the downloads are ahead
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// the connections to a host, resized when the setting changes
+struct HostLimit {
+    semaphore: Arc<Semaphore>,
+    /// the limit, and the permits to take back once the connections holding them finish
+    state: Mutex<(usize, usize)>,
+}
+
+impl HostLimit {
+    fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            state: Mutex::new((limit, 0)),
+        }
+    }
+
+    /// follow the setting, which is read in the lock so the latest one always wins
+    fn resize(&self, max_per_host: &AtomicUsize) {
+        let mut state = self.state.lock().unwrap();
+        let limit = max_per_host.load(Ordering::Relaxed);
+        let (current, owed) = &mut *state;
+        if limit > *current {
+            let added = limit - *current;
+            let repaid = added.min(*owed);
+            *owed -= repaid;
+            self.semaphore.add_permits(added - repaid);
+        } else {
+            *owed += *current - limit;
+        }
+        *owed -= self.semaphore.forget_permits(*owed);
+        *current = limit;
+    }
+
+    /// whether a permit just acquired is owed, then it is taken back instead of used
+    fn repay(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.1 == 0 {
+            return false;
+        }
+        state.1 -= 1;
+        true
+    }
+}
+
+pub struct DownloadLimits {
+    speed_limit: AtomicU64,
+    bucket: Mutex<TokenBucket>,
+    max_per_host: AtomicUsize,
+    hosts: DashMap<String, Arc<HostLimit>>,
+}
+
+impl Default for DownloadLimits {
+    fn default() -> Self {
+        let settings = DownloadSettings::default();
+        Self {
+            speed_limit: AtomicU64::new(settings.speed_limit),
+            bucket: Mutex::new(TokenBucket {
+                tokens: 0.0,
+                refilled_at: Instant::now(),
+            }),
+            max_per_host: AtomicUsize::new(settings.max_per_host),
+            hosts: DashMap::new(),
+        }
+    }
+}
+
+impl DownloadLimits {
+    /// follow the settings, the connections already made are kept
+    /// and those over a lowered limit are not replaced once they finish
+    pub fn configure(&self, settings: &DownloadSettings) {
+        self.speed_limit
+            .store(settings.speed_limit, Ordering::Relaxed);
+        let max_per_host = settings.max_per_host.max(1);
+        if self.max_per_host.swap(max_per_host, Ordering::Relaxed) != max_per_host {
+            for host in self.hosts.iter() {
+                host.resize(&self.max_per_host);
+            }
+        }
+    }
+
+    /// wait until the bytes received are within the speed limit
+    pub async fn consume(&self, bytes: u64) {
+        let rate = self.speed_limit.load(Ordering::Relaxed) as f64;
+        if rate == 0.0 {
+            return;
+        }
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let now = Instant::now();
+            // a burst of a second at most
+            bucket.tokens = (bucket.tokens
+                + now.duration_since(bucket.refilled_at).as_secs_f64() * rate)
+                .min(rate);
+            bucket.refilled_at = now;
+            bucket.tokens -= bytes as f64;
+            Duration::from_secs_f64((-bucket.tokens).max(0.0) / rate)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// a connection to the host of the url, waits while the host has too many
+    pub async fn connect(&self, url: &str) -> OwnedSemaphorePermit {
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        let host = self
+            .hosts
+            .entry(host)
+            .or_insert_with(|| Arc::new(HostLimit::new(self.max_per_host.load(Ordering::Relaxed))))
+            .clone();
+        // a host added while the setting changes may have missed the resizing
+        host.resize(&self.max_per_host);
+        loop {
+            let permit = Arc::clone(&host.semaphore)
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed");
+            if !host.repay() {
+                return permit;
+            }
+            permit.forget();
+        }
+    }
+}
+
+/// the bytes received and the failed requests of a task, sampled by its adaptive concurrency
+#[derive(Default)]
+pub struct Throughput {
+    received: AtomicU64,
+    failures: AtomicU64,
+}
+
+impl Throughput {
+    pub fn receive(&self, bytes: u64) {
+        self.received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// a request failed, which makes the adaptive concurrency back off
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// the files a task downloads at once
+pub struct Concurrency {
+    semaphore: Arc<Semaphore>,
+    throughput: Arc<Throughput>,
+}
+
+impl Concurrency {
+    /// a fixed limit, or a limit adjusted in the background until the returned handle is aborted
+    pub fn new(settings: &DownloadSettings) -> (Self, Option<tokio::task::JoinHandle<()>>) {
+        let max = settings.max_concurrency.max(1);
+        let throughput = Arc::new(Throughput::default());
+        if !settings.adaptive {
+            return (
+                Self {
+                    semaphore: Arc::new(Semaphore::new(max)),
+                    throughput,
+                },
+                None,
+            );
+        }
+        let initial = max.div_ceil(2);
+        let semaphore = Arc::new(Semaphore::new(initial));
+        let handle = tokio::spawn(adapt(
+            Arc::clone(&semaphore),
+            initial,
+            max,
+            Arc::clone(&throughput),
+        ));
+        (
+            Self {
+                semaphore,
+                throughput,
+            },
+            Some(handle),
+        )
+    }
+
+    /// what the downloads of the task report to
+    pub fn throughput(&self) -> Arc<Throughput> {
+        Arc::clone(&self.throughput)
+    }
+
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        Arc::clone(&self.semaphore)
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed")
+    }
+}
+
+/// grow the limit while the throughput grows, halve it on errors
+/// and step back when the throughput drops
+async fn adapt(
+    semaphore: Arc<Semaphore>,
+    mut limit: usize,
+    max: usize,
+    throughput: Arc<Throughput>,
+) {
+    let mut last_received = throughput.received.load(Ordering::Relaxed);
+    let mut last_failures = throughput.failures.load(Ordering::Relaxed);
+    let mut last_rate = 0.0;
+    // the permits to take back once the files holding them finish
+    let mut owed = 0;
+    loop {
+        tokio::time::sleep(ADAPT_INTERVAL).await;
+        let received = throughput.received.load(Ordering::Relaxed);
+        let failures = throughput.failures.load(Ordering::Relaxed);
+        let rate = (received - last_received) as f64 / ADAPT_INTERVAL.as_secs_f64();
+        let next = next_limit(limit, max, rate, last_rate, failures - last_failures);
+        if next > limit {
+            let added = next - limit;
+            let repaid = added.min(owed);
+            owed -= repaid;
+            semaphore.add_permits(added - repaid);
+        } else {
+            owed += limit - next;
+        }
+        owed -= semaphore.forget_permits(owed);
+        if next != limit {
+            log::debug!(
+                "download concurrency {} -> {}, {:.0} B/s",
+                limit,
+                next,
+                rate
+            );
+        }
+        limit = next;
+        last_received = received;
+        last_failures = failures;
+        last_rate = rate;
+    }
+}
+
+fn next_limit(limit: usize, max: usize, rate: f64, last_rate: f64, failures: u64) -> usize {
+    if failures > 0 {
+        (limit / 2).max(1)
+    } else if rate > last_rate * 1.05 {
+        (limit + 1).min(max)
+    } else if rate < last_rate * 0.8 {
+        (limit - 1).max(1)
+    } else {
+        limit
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn next_limit_test() {
+    assert_eq!(next_limit(4, 8, 2000.0, 1000.0, 0), 5);
+    assert_eq!(next_limit(8, 8, 2000.0, 1000.0, 0), 8);
+    assert_eq!(next_limit(5, 8, 1000.0, 1000.0, 0), 5);
+    assert_eq!(next_limit(5, 8, 500.0, 1000.0, 0), 4);
+    assert_eq!(next_limit(6, 8, 2000.0, 1000.0, 2), 3);
+    assert_eq!(next_limit(1, 8, 0.0, 1000.0, 1), 1);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn speed_limit_test() {
+    let limits = DownloadLimits::default();
+    limits.configure(&DownloadSettings {
+        speed_limit: 100_000,
+        ..Default::default()
+    });
+    let started = Instant::now();
+    for _ in 0..5 {
+        limits.consume(10_000).await;
+    }
+    // 50 KB at 100 KB/s
+    assert!(started.elapsed() >= Duration::from_millis(450));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn max_per_host_test() {
+    let limits = DownloadLimits::default();
+    let settings = |max_per_host| DownloadSettings {
+        max_per_host,
+        ..Default::default()
+    };
+    let connect = || tokio::time::timeout(Duration::from_millis(100), limits.connect("http://a/"));
+    limits.configure(&settings(2));
+    let first = connect().await.unwrap();
+    let second = connect().await.unwrap();
+    // lowered while the connections are held, the one finished first is not replaced
+    limits.configure(&settings(1));
+    drop(first);
+    assert!(connect().await.is_err());
+    drop(second);
+    let third = connect().await.unwrap();
+    limits.configure(&settings(2));
+    let _fourth = connect().await.unwrap();
+    assert!(connect().await.is_err());
+    drop(third);
+}
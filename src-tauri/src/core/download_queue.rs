@@ -0,0 +1,245 @@
+//! The journal of the download tasks, kept in the config directory so a task interrupted
+//! by closing the launcher can be resumed on the next start.
+//! Only what the task is for is journaled, the files are listed again when it resumes,
+//! and those already verified by sha1 are skipped.
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const FILE_NAME: &str = "download_queue.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadQueueError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Json parsing failed: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("queued download {0} not found")]
+    NotFound(u64),
+
+    #[error("queued download {0} is running")]
+    Running(u64),
+
+    #[error("invalid instance name: {0}")]
+    InvalidInstanceName(String),
+}
+
+/// what a queued download does
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DownloadJob {
+    MinecraftVersion {
+        version_id: String,
+        instance_name: String,
+        /// the repository active when the download started
+        repo_path: PathBuf,
+    },
+}
+
+impl DownloadJob {
+    /// the instance name is a folder name in the repository, it can't lead out of it
+    fn check(&self) -> Result<(), DownloadQueueError> {
+        match self {
+            DownloadJob::MinecraftVersion { instance_name, .. } => {
+                if instance_name.is_empty()
+                    || instance_name == "."
+                    || instance_name.contains("..")
+                    || instance_name.contains(['/', '\\'])
+                {
+                    return Err(DownloadQueueError::InvalidInstanceName(
+                        instance_name.clone(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the folder the job populates, removed when the job is discarded if it created it
+    fn own_dir(&self) -> PathBuf {
+        match self {
+            DownloadJob::MinecraftVersion {
+                instance_name,
+                repo_path,
+                ..
+            } => repo_path.join("versions").join(instance_name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QueuedDownload {
+    pub id: u64,
+    pub job: DownloadJob,
+    /// unix ms
+    pub queued_at: i64,
+    /// whether the folder of the job didn't exist when it was queued,
+    /// an existing one is never removed
+    #[serde(default)]
+    created_dir: bool,
+    /// whether it is running in this session, the ones loaded from the journal are stale
+    #[serde(skip)]
+    running: bool,
+}
+
+pub struct DownloadQueue {
+    path: PathBuf,
+    downloads: Mutex<Vec<QueuedDownload>>,
+}
+
+impl DownloadQueue {
+    /// load the journal, a corrupted one is dropped as nothing can be resumed from it
+    pub fn load(config_dir: &Path) -> Self {
+        let path = config_dir.join(FILE_NAME);
+        let downloads = match std::fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|err| {
+                log::warn!("download queue corrupted: {}", err);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            downloads: Mutex::new(downloads),
+        }
+    }
+
+    /// journal a download which starts running, returns its id in the queue
+    pub fn push(&self, job: DownloadJob) -> Result<u64, DownloadQueueError> {
+        job.check()?;
+        let mut downloads = self.downloads.lock().unwrap();
+        let id = downloads
+            .iter()
+            .map(|download| download.id + 1)
+            .max()
+            .unwrap_or(0);
+        downloads.push(QueuedDownload {
+            id,
+            created_dir: !job.own_dir().exists(),
+            job,
+            queued_at: chrono::Local::now().timestamp_millis(),
+            running: true,
+        });
+        self.save(&downloads)?;
+        Ok(id)
+    }
+
+    /// the downloads not running, left by the last session or failed in this one
+    pub fn stale(&self) -> Vec<QueuedDownload> {
+        let downloads = self.downloads.lock().unwrap();
+        downloads
+            .iter()
+            .filter(|download| !download.running)
+            .cloned()
+            .collect()
+    }
+
+    /// take a stale download to run it again
+    pub fn resume(&self, id: u64) -> Result<DownloadJob, DownloadQueueError> {
+        let mut downloads = self.downloads.lock().unwrap();
+        let download = downloads
+            .iter_mut()
+            .find(|download| download.id == id)
+            .ok_or(DownloadQueueError::NotFound(id))?;
+        if download.running {
+            return Err(DownloadQueueError::Running(id));
+        }
+        download.running = true;
+        Ok(download.job.clone())
+    }
+
+    /// the download stopped, it is kept in the queue to be resumed
+    pub fn stop(&self, id: u64) {
+        let mut downloads = self.downloads.lock().unwrap();
+        if let Some(download) = downloads.iter_mut().find(|download| download.id == id) {
+            download.running = false;
+        }
+    }
+
+    /// the download completed or is cancelled
+    pub fn finish(&self, id: u64) -> Result<(), DownloadQueueError> {
+        let mut downloads = self.downloads.lock().unwrap();
+        downloads.retain(|download| download.id != id);
+        self.save(&downloads)
+    }
+
+    /// drop a stale download and the half-populated folder it created.
+    /// the shared libraries and assets are kept, other instances may use them
+    pub fn discard(&self, id: u64) -> Result<(), DownloadQueueError> {
+        let mut downloads = self.downloads.lock().unwrap();
+        let index = downloads
+            .iter()
+            .position(|download| download.id == id)
+            .ok_or(DownloadQueueError::NotFound(id))?;
+        if downloads[index].running {
+            return Err(DownloadQueueError::Running(id));
+        }
+        let download = downloads.remove(index);
+        self.save(&downloads)?;
+        // the journal may be edited by hand
+        if !download.created_dir || download.job.check().is_err() {
+            return Ok(());
+        }
+        let own_dir = download.job.own_dir();
+        if own_dir.is_dir() {
+            log::info!("removing {:?} left by the discarded download", own_dir);
+            std::fs::remove_dir_all(own_dir)?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, downloads: &[QueuedDownload]) -> Result<(), DownloadQueueError> {
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, serde_json::to_vec_pretty(downloads)?)?;
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn download_queue_test() {
+    let dir = std::env::temp_dir().join("pcl-proto-download-queue-test");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("versions/existing")).unwrap();
+    let job = |instance_name: &str| DownloadJob::MinecraftVersion {
+        version_id: "1.21".to_string(),
+        instance_name: instance_name.to_string(),
+        repo_path: dir.clone(),
+    };
+    let queue = DownloadQueue::load(&dir);
+    for name in ["", "..", "../1.21", "a/b", "a\\b"] {
+        assert!(matches!(
+            queue.push(job(name)),
+            Err(DownloadQueueError::InvalidInstanceName(_))
+        ));
+    }
+    let id = queue.push(job("1.21")).unwrap();
+    let existing = queue.push(job("existing")).unwrap();
+    let completed = queue.push(job("1.21")).unwrap();
+    queue.finish(completed).unwrap();
+    assert!(queue.stale().is_empty());
+    assert!(matches!(
+        queue.discard(id),
+        Err(DownloadQueueError::Running(_))
+    ));
+    // the download populates the folder
+    std::fs::create_dir_all(dir.join("versions/1.21")).unwrap();
+
+    // the running ones are stale after a restart
+    let queue = DownloadQueue::load(&dir);
+    assert_eq!(queue.stale().len(), 2);
+    queue.resume(id).unwrap();
+    assert_eq!(queue.stale().len(), 1);
+    queue.stop(id);
+    queue.discard(id).unwrap();
+    assert!(!dir.join("versions/1.21").exists());
+    // the folder was there before the download
+    queue.discard(existing).unwrap();
+    assert!(dir.join("versions/existing").is_dir());
+    assert!(DownloadQueue::load(&dir).stale().is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -2,26 +2,38 @@
 //! Designed for Minecraft version and mod downloads.
 //! Supports mutli task parallel and mspc channel.
 use crate::{
-    core::api_client::{
-        McApiError,
-        game::{DownloadInfo, VersionDetails},
+    core::{
+        api_client::{
+            ApiBases, McApiError,
+            game::{AssetIndex, DownloadInfo, VersionDetails},
+        },
+        download_limit::{
+            Concurrency, DownloadLimits, DownloadSettings, SHARED_LIMITS, Throughput,
+        },
+        task_manager::TaskSignal,
     },
     setup::{ConfigManager, constants::USER_AGENT},
     util::file,
 };
 use dashmap::DashMap;
 use futures_util::StreamExt;
-use reqwest::Client;
+use reqwest::{Client, StatusCode, header::RANGE};
 use std::{
     fs,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 use tokio::{
     io::AsyncWriteExt,
     sync::{Mutex, mpsc},
 };
 
+/// a source which can't be connected in time is failed over
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// a connection stalled this long is dropped, the part is resumed by the next try
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
 // ---------------- 🌟 Progress Types 🌟 ----------------
 
 #[derive(serde::Serialize)]
@@ -47,6 +59,8 @@ pub enum TaskStatus {
     Running = 1,
     Completed = 2,
     Failed = 3,
+    Paused = 4,
+    Cancelled = 5,
 }
 
 /// inner progress update structure
@@ -75,13 +89,71 @@ pub struct DownloadFile {
 #[derive(Clone)]
 pub struct Downloader {
     client: Client,
+    /// the providers to download from in order, a file fails over to the next one
+    sources: Arc<Vec<ApiBases>>,
+    /// the task the downloads belong to, which may pause or cancel them
+    signal: TaskSignal,
+    settings: DownloadSettings,
+    /// the bandwidth and the connections shared with the other downloaders
+    limits: Arc<DownloadLimits>,
+    /// sampled by the adaptive concurrency of the task
+    throughput: Arc<Throughput>,
 }
 
 impl Downloader {
-    pub fn new() -> Self {
+    /// download from the provider and fail over to the others
+    pub fn new(api_bases: &ApiBases) -> Self {
         Self {
-            client: Client::builder().user_agent(USER_AGENT).build().unwrap(),
+            client: Client::builder()
+                .user_agent(USER_AGENT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .read_timeout(READ_TIMEOUT)
+                .build()
+                .unwrap(),
+            sources: Arc::new(api_bases.failover()),
+            signal: TaskSignal::none(),
+            settings: DownloadSettings::default(),
+            limits: Arc::clone(&SHARED_LIMITS),
+            throughput: Arc::default(),
+        }
+    }
+
+    /// a downloader following the api provider and the download settings in the config
+    pub async fn from_config() -> Self {
+        let config = ConfigManager::instance();
+        let settings = config
+            .app_state
+            .lock()
+            .await
+            .pcl_setup_info
+            .download
+            .clone();
+        Self::new(&config.api_client.api_bases_async().await).with_settings(settings)
+    }
+
+    /// the shared limits follow the settings of the latest downloader
+    pub fn with_settings(mut self, settings: DownloadSettings) -> Self {
+        self.limits.configure(&settings);
+        self.settings = settings;
+        self
+    }
+
+    /// the downloads are paused and cancelled with the task
+    pub fn with_signal(mut self, signal: TaskSignal) -> Self {
+        self.signal = signal;
+        self
+    }
+
+    /// the url on every source, without duplicates
+    fn candidate_urls(&self, url: &str) -> Vec<String> {
+        let mut urls: Vec<String> = Vec::new();
+        for source in self.sources.iter() {
+            let url = source.mirror_url(url);
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
         }
+        urls
     }
 
     /// start downloading a single file
@@ -90,6 +162,12 @@ impl Downloader {
         options: DownloadConfig,
         progress_tx: mpsc::Sender<ProgressUpdate>,
     ) -> Result<(), McApiError> {
+        let mut signal = self.signal.clone();
+        let part_path = part_path(&options.out_path);
+        // the files not started yet wait here while the task is paused
+        if !signal.proceed().await {
+            return Err(self.discard(&options, &part_path, &progress_tx).await);
+        }
         progress_tx
             .send(ProgressUpdate {
                 file_index: options.file_index,
@@ -114,11 +192,57 @@ impl Downloader {
                 .await?;
             return Ok(());
         } else {
-            let mut retries: u8 = 3;
+            let urls = self.candidate_urls(&options.info.url);
+            let mut source = 0;
+            // a source is retried once before failing over
+            let mut source_failed = false;
+            let mut retries = 3 * urls.len();
+            let mut last_error = None;
             while retries > 0 {
-                self.http_download_inner(&options, progress_tx.clone())
-                    .await?;
-                if file::check_sha1(&options.out_path, &options.info.sha1)? {
+                retries -= 1;
+                let url = &urls[source];
+                // the part is kept when the connection breaks, so the next try resumes from it
+                match self
+                    .http_download_inner(
+                        &options,
+                        url,
+                        &part_path,
+                        progress_tx.clone(),
+                        &mut signal,
+                    )
+                    .await
+                {
+                    Ok(()) => {}
+                    Err(McApiError::Interrupted) => {
+                        // pausing doesn't use up the retries
+                        retries += 1;
+                        self.wait_resumed(&options, &part_path, &progress_tx, &mut signal)
+                            .await?;
+                        continue;
+                    }
+                    Err(McApiError::RequestError(err)) => {
+                        self.throughput.record_failure();
+                        log::warn!(
+                            "download failed, url: {}, retry remaining: {}, {}",
+                            url,
+                            retries,
+                            err
+                        );
+                        // the mirror doesn't have the file, or keeps failing to connect or timing out
+                        if err.status().is_some() || source_failed {
+                            source = (source + 1) % urls.len();
+                            source_failed = false;
+                        } else {
+                            source_failed = true;
+                        }
+                        last_error = Some(McApiError::RequestError(err));
+                        continue;
+                    }
+                    Err(err) => return Err(err),
+                }
+                if file::check_sha1(&part_path, &options.info.sha1)? {
+                    // the file appears only when it is complete
+                    fs::rename(&part_path, &options.out_path)?;
                     progress_tx
                         .send(ProgressUpdate {
                             file_index: options.file_index,
@@ -131,43 +255,170 @@ impl Downloader {
                         .await?;
                     return Ok(());
                 }
-                log::warn!(
-                    "sha1 mismatch, file: {:?}, retry remaining: {}",
-                    options.info.path,
-                    retries
-                );
-                retries -= 1;
+                log::warn!("sha1 mismatch, url: {}, retry remaining: {}", url, retries);
+                // resuming a corrupted part never makes it right
+                fs::remove_file(&part_path)?;
+                source = (source + 1) % urls.len();
+                source_failed = false;
+                last_error = Some(McApiError::Sha1Mismatch(options.out_path.clone()));
+                progress_tx
+                    .send(ProgressUpdate {
+                        file_index: options.file_index,
+                        progress: FileProgress::default(),
+                        item_id: options.task_item_id,
+                    })
+                    .await?;
             }
+            progress_tx
+                .send(ProgressUpdate {
+                    file_index: options.file_index,
+                    progress: FileProgress {
+                        downloaded_bytes: 0,
+                        status: TaskStatus::Failed,
+                    },
+                    item_id: options.task_item_id,
+                })
+                .await?;
+            Err(last_error.unwrap_or(McApiError::Sha1Mismatch(options.out_path)))
         }
-        progress_tx
+    }
+
+    /// report the file paused with the part kept, and wait until the task is resumed.
+    /// the part is removed if the task is cancelled instead
+    async fn wait_resumed(
+        &self,
+        options: &DownloadConfig,
+        part_path: &Path,
+        progress_tx: &mpsc::Sender<ProgressUpdate>,
+        signal: &mut TaskSignal,
+    ) -> Result<(), McApiError> {
+        if !signal.is_cancelled() {
+            let downloaded_bytes = match tokio::fs::metadata(part_path).await {
+                Ok(metadata) => metadata.len(),
+                Err(_) => 0,
+            };
+            progress_tx
+                .send(ProgressUpdate {
+                    file_index: options.file_index,
+                    progress: FileProgress {
+                        downloaded_bytes,
+                        status: TaskStatus::Paused,
+                    },
+                    item_id: options.task_item_id,
+                })
+                .await?;
+            if signal.proceed().await {
+                return Ok(());
+            }
+        }
+        Err(self.discard(options, part_path, progress_tx).await)
+    }
+
+    /// remove the part of a cancelled file and report it
+    async fn discard(
+        &self,
+        options: &DownloadConfig,
+        part_path: &Path,
+        progress_tx: &mpsc::Sender<ProgressUpdate>,
+    ) -> McApiError {
+        if let Err(err) = tokio::fs::remove_file(part_path).await
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            return err.into();
+        }
+        let report = progress_tx
             .send(ProgressUpdate {
                 file_index: options.file_index,
                 progress: FileProgress {
                     downloaded_bytes: 0,
-                    status: TaskStatus::Failed,
+                    status: TaskStatus::Cancelled,
                 },
                 item_id: options.task_item_id,
             })
-            .await?;
-        return Err(McApiError::Sha1Mismatch(options.out_path));
+            .await;
+        match report {
+            Ok(()) => McApiError::Cancelled,
+            Err(err) => err.into(),
+        }
     }
 
-    /// the actual process of downloading a single file
+    /// the actual process of downloading a single file into the part file,
+    /// which is resumed if the server supports range requests.
+    /// stops with `Interrupted` once the task is paused or cancelled, the part is kept
     async fn http_download_inner(
         &self,
         option: &DownloadConfig,
+        url: &str,
+        part_path: &Path,
         progress_tx: mpsc::Sender<ProgressUpdate>,
+        signal: &mut TaskSignal,
     ) -> Result<(), McApiError> {
-        let response = self.client.get(&option.info.url).send().await?;
-        let parent_path = option.out_path.parent().unwrap();
+        let parent_path = part_path.parent().unwrap();
         if !parent_path.is_dir() {
             fs::create_dir_all(parent_path)?;
         }
-        let mut file = tokio::fs::File::create(&option.out_path).await?;
+        let mut downloaded = match tokio::fs::metadata(part_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+        // a part larger than the file is from something else
+        if option.info.size > 0 && downloaded > option.info.size {
+            tokio::fs::remove_file(part_path).await?;
+            downloaded = 0;
+        }
+        // a task waiting for a connection to the host is paused and cancelled as well
+        let _connection = tokio::select! {
+            connection = self.limits.connect(url) => connection,
+            _ = signal.interrupted() => return Err(McApiError::Interrupted),
+        };
+        let mut request = self.client.get(url);
+        if downloaded > 0 {
+            request = request.header(RANGE, format!("bytes={}-", downloaded));
+        }
+        let response = tokio::select! {
+            response = request.send() => response?,
+            _ = signal.interrupted() => return Err(McApiError::Interrupted),
+        };
+        // the part is already complete, or it is wrong and the sha1 check tells
+        if downloaded > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Ok(());
+        }
+        let response = response.error_for_status()?;
+        let mut file = if response.status() == StatusCode::PARTIAL_CONTENT {
+            log::debug!("resume {:?} from {}", part_path, downloaded);
+            tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(part_path)
+                .await?
+        } else {
+            // range not supported, start over
+            if downloaded > 0 {
+                progress_tx
+                    .send(ProgressUpdate {
+                        file_index: option.file_index,
+                        progress: FileProgress::default(),
+                        item_id: option.task_item_id,
+                    })
+                    .await?;
+            }
+            downloaded = 0;
+            tokio::fs::File::create(part_path).await?
+        };
         let mut stream = response.bytes_stream();
-        let mut downloaded: u64 = 0;
-        while let Some(chunk) = stream.next().await {
+        loop {
+            let chunk = tokio::select! {
+                chunk = stream.next() => chunk,
+                _ = signal.interrupted() => {
+                    file.flush().await?;
+                    return Err(McApiError::Interrupted);
+                }
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
             let chunk = chunk?;
+            self.throughput.receive(chunk.len() as u64);
+            self.limits.consume(chunk.len() as u64).await;
             file.write_all(&chunk).await?;
             downloaded += chunk.len() as u64;
             progress_tx
@@ -186,14 +437,62 @@ impl Downloader {
         Ok(())
     }
 
-    /// simply download a file and check its sha1
-    async fn download_without_report(
+    /// download all the files of the task items in parallel and report the progress,
+    /// returns the count of files failed to download, cancelled ones included
+    pub async fn download_task_items(
+        &self,
+        task_items: Vec<(Arc<Mutex<TaskItem>>, Vec<DownloadConfig>)>,
+        on_event: tauri::ipc::Channel<TaskItemReport>,
+    ) -> Result<usize, tokio::task::JoinError> {
+        // set up the minotor
+        let (progress_tx, progress_rx) = mpsc::channel(100);
+        let mut monitor = ProgressMonitor::default();
+        let mut all_options = Vec::new();
+        for (task_item, download_options) in task_items {
+            monitor = monitor.with_item(task_item).await;
+            all_options.extend(download_options);
+        }
+        let monitor_handle = tokio::task::spawn(async move {
+            monitor.start_monitoring(progress_rx, on_event).await;
+        });
+        let (concurrency, adapting) = Concurrency::new(&self.settings);
+        let mut downloader = self.clone();
+        downloader.throughput = concurrency.throughput();
+        let concurrency = Arc::new(concurrency);
+        let download_stream = futures_util::stream::iter(all_options.into_iter().map(|options| {
+            let downloader = downloader.clone();
+            let tx = progress_tx.clone();
+            let concurrency = Arc::clone(&concurrency);
+            async move {
+                let _permit = concurrency.acquire().await;
+                if let Err(e) = downloader.start_download(options, tx).await {
+                    log::error!("{}", e);
+                    return false;
+                }
+                true
+            }
+        }));
+        // wait until all the tasks has finished
+        let failed_count = download_stream
+            .buffer_unordered(self.settings.max_concurrency.max(1))
+            .filter(|succeeded| futures_util::future::ready(!succeeded))
+            .count()
+            .await;
+        if let Some(adapting) = adapting {
+            adapting.abort();
+        }
+        drop(progress_tx);
+        monitor_handle.await?;
+        Ok(failed_count)
+    }
+
+    /// simply download a file and check its sha1.
+    /// a paused task starts the file over once it is resumed
+    pub async fn download_without_report(
         &self,
         info: &DownloadInfo,
         base_path: &Path,
     ) -> Result<PathBuf, McApiError> {
-        let response = self.client.get(&info.url).send().await?;
-        let bytes = response.bytes().await?;
         let out_path = base_path.join(
             info.path
                 .as_deref()
@@ -207,18 +506,64 @@ impl Downloader {
         if !parent_path.is_dir() {
             fs::create_dir_all(parent_path)?;
         }
-        let mut file = tokio::fs::File::create(&out_path).await?;
-        file.write(&bytes).await?;
-        file.flush().await?;
-        drop(file);
-        if !crate::util::file::check_sha1(&out_path, &info.sha1)? {
-            Err(McApiError::Sha1Mismatch(out_path))
-        } else {
-            Ok(out_path)
+        let part_path = part_path(&out_path);
+        let mut signal = self.signal.clone();
+        let mut last_error = McApiError::Sha1Mismatch(out_path.clone());
+        for url in self.candidate_urls(&info.url) {
+            let fetched = loop {
+                match self.fetch_bytes(&url, &mut signal).await {
+                    Err(McApiError::Interrupted) => {
+                        if !signal.proceed().await {
+                            return Err(McApiError::Cancelled);
+                        }
+                    }
+                    fetched => break fetched,
+                }
+            };
+            let bytes = match fetched {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    log::warn!("download failed, url: {}, {}", url, err);
+                    last_error = err;
+                    continue;
+                }
+            };
+            tokio::fs::write(&part_path, &bytes).await?;
+            if crate::util::file::check_sha1(&part_path, &info.sha1)? {
+                fs::rename(&part_path, &out_path)?;
+                return Ok(out_path);
+            }
+            log::warn!("sha1 mismatch, url: {}", url);
+            tokio::fs::remove_file(&part_path).await?;
+            last_error = McApiError::Sha1Mismatch(out_path.clone());
+        }
+        Err(last_error)
+    }
+
+    /// stops with `Interrupted` once the task is paused or cancelled
+    async fn fetch_bytes(&self, url: &str, signal: &mut TaskSignal) -> Result<Vec<u8>, McApiError> {
+        let fetch = async {
+            let _connection = self.limits.connect(url).await;
+            let response = self.client.get(url).send().await?.error_for_status()?;
+            let bytes = response.bytes().await?;
+            self.throughput.receive(bytes.len() as u64);
+            self.limits.consume(bytes.len() as u64).await;
+            Ok::<_, reqwest::Error>(bytes.to_vec())
+        };
+        tokio::select! {
+            bytes = fetch => Ok(bytes?),
+            _ = signal.interrupted() => Err(McApiError::Interrupted),
         }
     }
 }
 
+/// where a file is downloaded to before it is complete and verified
+pub fn part_path(out_path: &Path) -> PathBuf {
+    let mut file_name = out_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    out_path.with_file_name(file_name)
+}
+
 // ---------------- 🌟 TaskItem 🌟 ----------------
 
 #[derive(serde::Serialize)]
@@ -275,7 +620,7 @@ impl TaskItem {
         self.downloaded_size = self.files.iter().fold(0, |acc, file| {
             acc + match file.progress.status {
                 TaskStatus::Completed => file.info.size,
-                TaskStatus::Running => file.progress.downloaded_bytes,
+                TaskStatus::Running | TaskStatus::Paused => file.progress.downloaded_bytes,
                 _ => 0,
             }
         });
@@ -285,14 +630,15 @@ impl TaskItem {
             .filter(|file| file.progress.status != TaskStatus::Completed)
             .count();
         self.progress = self.downloaded_size as f64 / self.total_size as f64;
+        let any_file = |status| self.files.iter().any(|file| file.progress.status == status);
         self.status = if self.remaining_files == 0 {
             TaskStatus::Completed
-        } else if self
-            .files
-            .iter()
-            .any(|file| file.progress.status == TaskStatus::Failed)
-        {
+        } else if any_file(TaskStatus::Cancelled) {
+            TaskStatus::Cancelled
+        } else if any_file(TaskStatus::Failed) {
             TaskStatus::Failed
+        } else if any_file(TaskStatus::Paused) && !any_file(TaskStatus::Running) {
+            TaskStatus::Paused
         } else {
             TaskStatus::Running
         };
@@ -353,6 +699,8 @@ impl ProgressMonitor {
                 .expect("[progress minitor] wrong item id's got!!!");
             let mut task_item_to_report = task_item_to_report.lock().await;
             let last_download_bytes = task_item_to_report.downloaded_size;
+            // a file may start over after its part turns out to be corrupted
+            let restarted = update.progress.status == TaskStatus::Pending;
             task_item_to_report.update_file_progress(update.file_index, update.progress);
             let mut report: TaskItemReport = TaskItemReport::from(&*task_item_to_report);
             let this_downloaded_bytes = task_item_to_report.downloaded_size;
@@ -360,12 +708,18 @@ impl ProgressMonitor {
             drop(task_item_to_report);
             let now = tokio::time::Instant::now();
             let during = now.duration_since(last_sent_time);
+            let stopped = matches!(
+                this_status,
+                TaskStatus::Failed | TaskStatus::Paused | TaskStatus::Cancelled
+            );
             if during.as_millis() >= MIN_INTERVAL_MS
-                || matches!(this_status, TaskStatus::Completed | TaskStatus::Failed)
+                || this_status == TaskStatus::Completed
+                || stopped
             {
                 assert!(
                     this_downloaded_bytes >= last_download_bytes
-                        || this_status == TaskStatus::Failed,
+                        || matches!(this_status, TaskStatus::Failed | TaskStatus::Cancelled)
+                        || restarted,
                     "wrong update at: index: {:?}, report: {:?}, downloaded: {}, last: {}",
                     update.file_index,
                     report,
@@ -373,9 +727,10 @@ impl ProgressMonitor {
                     last_download_bytes
                 );
 
-                if this_status != TaskStatus::Failed {
+                if !stopped {
                     report.set_speed(
-                        (this_downloaded_bytes - last_download_bytes) as f64 / during.as_secs_f64(),
+                        this_downloaded_bytes.saturating_sub(last_download_bytes) as f64
+                            / during.as_secs_f64(),
                     );
                 }
                 on_event.send(report).expect("report corrupted");
@@ -419,6 +774,10 @@ impl TaskItemReport {
 
 pub mod minecraft_resource {
     use super::*;
+    use crate::core::{
+        download_queue::{DownloadJob, QueuedDownload},
+        task_manager::TaskManager,
+    };
 
     /// get cached version json from the temp dir if it exists,
     /// then copy it to the vesion folder and return the value of it
@@ -443,6 +802,7 @@ pub mod minecraft_resource {
     /// 2. Download the version jar file
     /// 3. Download the libraries to support the version
     /// 4. Download the resources
+    /// The task is journaled in the download queue until it completes or is cancelled.
     #[tauri::command(rename_all = "snake_case")]
     pub async fn download_minecraft_version(
         state: tauri::State<'_, Arc<tokio::sync::Mutex<crate::setup::AppState>>>,
@@ -451,10 +811,147 @@ pub mod minecraft_resource {
         task_id: i32,
         instance_name: &str,
     ) -> Result<(), String> {
-        let downloader = Downloader::new();
+        let (repo_path, task_manager) = {
+            let state = state.lock().await;
+            (
+                state.active_repo_path.clone(),
+                Arc::clone(&state.task_manager),
+            )
+        };
+        let job = DownloadJob::MinecraftVersion {
+            version_id: version_id.to_string(),
+            instance_name: instance_name.to_string(),
+            repo_path,
+        };
+        let queue_id = ConfigManager::instance()
+            .download_queue
+            .push(job.clone())
+            .map_err(|err| err.to_string())?;
+        run_queued(task_manager, on_event, task_id, queue_id, job).await
+    }
+
+    /// resume a download left in the queue by the last session or failed before,
+    /// the files already downloaded are skipped
+    #[tauri::command(rename_all = "snake_case")]
+    pub async fn resume_queued_download(
+        state: tauri::State<'_, Arc<tokio::sync::Mutex<crate::setup::AppState>>>,
+        on_event: tauri::ipc::Channel<TaskItemReport>,
+        queue_id: u64,
+        task_id: i32,
+    ) -> Result<(), String> {
+        let task_manager = Arc::clone(&state.lock().await.task_manager);
+        let job = ConfigManager::instance()
+            .download_queue
+            .resume(queue_id)
+            .map_err(|err| err.to_string())?;
+        run_queued(task_manager, on_event, task_id, queue_id, job).await
+    }
+
+    /// the downloads in the queue which are not running
+    #[tauri::command]
+    pub async fn get_queued_downloads() -> Result<Vec<QueuedDownload>, ()> {
+        Ok(ConfigManager::instance().download_queue.stale())
+    }
+
+    /// drop a download from the queue, the instance folder it leaves is removed
+    #[tauri::command(rename_all = "snake_case")]
+    pub async fn discard_queued_download(queue_id: u64) -> Result<(), String> {
+        ConfigManager::instance()
+            .download_queue
+            .discard(queue_id)
+            .map_err(|err| err.to_string())
+    }
+
+    /// run a job of the queue as a task, it stays in the queue to be resumed if it fails
+    async fn run_queued(
+        task_manager: Arc<TaskManager>,
+        on_event: tauri::ipc::Channel<TaskItemReport>,
+        task_id: i32,
+        queue_id: u64,
+        job: DownloadJob,
+    ) -> Result<(), String> {
+        // the task can be paused and cancelled by its id until it finishes
+        let task = task_manager.register(task_id);
+        let result = match &job {
+            DownloadJob::MinecraftVersion {
+                version_id,
+                instance_name,
+                repo_path,
+            } => {
+                download_version(
+                    on_event,
+                    &task.signal,
+                    task_id,
+                    version_id,
+                    instance_name,
+                    repo_path,
+                )
+                .await
+            }
+        };
+        let download_queue = &ConfigManager::instance().download_queue;
+        if result.is_ok() || task.signal.is_cancelled() {
+            if let Err(err) = download_queue.finish(queue_id) {
+                log::warn!(
+                    "failed to remove download {} from the queue: {}",
+                    queue_id,
+                    err
+                );
+            }
+        } else {
+            download_queue.stop(queue_id);
+        }
+        result
+    }
+
+    /// a step fetching the version before the files, which is dropped once the task is paused
+    /// and started over once it is resumed
+    async fn interruptible<T, F: Future<Output = Result<T, McApiError>>>(
+        on_event: &tauri::ipc::Channel<TaskItemReport>,
+        signal: &TaskSignal,
+        task_id: i32,
+        mut step: impl FnMut() -> F,
+    ) -> Result<T, String> {
+        let mut signal = signal.clone();
+        let report = |status| {
+            on_event
+                .send(TaskItemReport {
+                    task_id,
+                    item_id: 0,
+                    files_remaining: 0,
+                    progress: 0.0,
+                    status,
+                    speed: None,
+                })
+                .map_err(|err| err.to_string())
+        };
+        loop {
+            tokio::select! {
+                result = step() => return result.map_err(|err| err.to_string()),
+                _ = signal.interrupted() => {}
+            }
+            if !signal.is_cancelled() {
+                report(TaskStatus::Paused)?;
+            }
+            if !signal.proceed().await {
+                report(TaskStatus::Cancelled)?;
+                return Err(McApiError::Cancelled.to_string());
+            }
+            report(TaskStatus::Running)?;
+        }
+    }
+
+    async fn download_version(
+        on_event: tauri::ipc::Channel<TaskItemReport>,
+        signal: &TaskSignal,
+        task_id: i32,
+        version_id: &str,
+        instance_name: &str,
+        repo: &Path,
+    ) -> Result<(), String> {
         log::info!("start a task of downloading mc: {}", version_id);
+        let downloader = Downloader::from_config().await.with_signal(signal.clone());
         // get the folder of this version
-        let repo = state.lock().await.active_repo_path.clone();
         let version_folder = repo.join(format!("versions/{}", instance_name));
         let assets_folder = repo.join("assets");
 
@@ -465,11 +962,12 @@ pub mod minecraft_resource {
             if let Ok(version_details_tmp) = try_get_temp_json(&version_id, &version_folder) {
                 version_details = version_details_tmp;
             } else {
-                version_details = ConfigManager::instance()
-                    .api_client
-                    .get_version_details(&version_id, &version_folder)
-                    .await
-                    .map_err(|err| err.to_string())?;
+                version_details = interruptible(&on_event, signal, task_id, || {
+                    ConfigManager::instance()
+                        .api_client
+                        .get_version_details(&version_id, &version_folder)
+                })
+                .await?;
             }
             // filter the not allowed libraries
             let libraries_download = {
@@ -477,7 +975,7 @@ pub mod minecraft_resource {
                 libraries
                     .iter()
                     .filter(|lib| lib.rule_allow())
-                    .map(|lib| lib.downloads.artifact.to_owned())
+                    .flat_map(|lib| lib.downloads_for_current())
                     .collect::<Vec<DownloadInfo>>()
             };
             // modify the jar path cause api don't provide it
@@ -487,35 +985,21 @@ pub mod minecraft_resource {
             // fetch the assets index
             // let assets_index_downloadInfo = version_details.asset_index;
             let assets_download = {
-                let asset_index_file = downloader
-                    .download_without_report(
-                        &version_details.asset_index,
-                        &assets_folder.join("indexes"),
-                    )
-                    .await
-                    .map_err(|err| err.to_string())?;
-                let reader = fs::File::open(asset_index_file).unwrap();
-                let asset_index: serde_json::Value = serde_json::from_reader(reader).unwrap();
-                let objects = asset_index["objects"].as_object().unwrap();
-                let resources_base = ConfigManager::instance()
-                    .api_client
-                    .api_bases_async()
-                    .await
-                    .resources_base;
-                objects
-                    .iter()
-                    .map(|(_path, value)| {
-                        let hash = value["hash"].as_str().unwrap();
-                        let size = value["size"].as_u64().unwrap();
-                        let path = format!("{}/{}", &hash[..2], hash);
-                        DownloadInfo {
-                            sha1: hash.to_string(),
-                            size,
-                            url: format!("{}/{}", resources_base, path),
-                            path: Some(path),
-                        }
-                    })
-                    .collect::<Vec<_>>()
+                let indexes_folder = assets_folder.join("indexes");
+                let asset_index_file = interruptible(&on_event, signal, task_id, || {
+                    downloader
+                        .download_without_report(&version_details.asset_index, &indexes_folder)
+                })
+                .await?;
+                let reader = fs::File::open(asset_index_file).map_err(|err| err.to_string())?;
+                let asset_index: AssetIndex =
+                    serde_json::from_reader(reader).map_err(|err| err.to_string())?;
+                asset_index
+                    .objects
+                    .values()
+                    .map(|object| object.download_info())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| err.to_string())?
             };
             (jar_download, libraries_download, assets_download)
         };
@@ -549,41 +1033,26 @@ pub mod minecraft_resource {
             repo.join("assets/objects"),
         );
 
-        // set up the minotor
-        let (progress_tx, progress_rx) = mpsc::channel(100);
-        let monitor = ProgressMonitor::default()
-            .with_item(Arc::clone(&task1))
-            .await
-            .with_item(Arc::clone(&task2))
-            .await
-            .with_item(Arc::clone(&task3))
-            .await;
-        let monitor_handle = tokio::task::spawn(async move {
-            monitor.start_monitoring(progress_rx, on_event).await;
-        });
-
         // start the actual downloading
-        const MAX_CONCURRENT_DOWNLOADS: usize = 8;
-        let mut all_options = Vec::new();
-        all_options.extend(download_options1);
-        all_options.extend(download_options2);
-        all_options.extend(download_options3);
-        let download_stream = futures_util::stream::iter(all_options.into_iter().map(|options| {
-            let downloader = downloader.clone();
-            let tx = progress_tx.clone();
-            async move {
-                if let Err(e) = downloader.start_download(options, tx).await {
-                    log::error!("{}", e);
-                }
-            }
-        }));
-        // wait until all the tasks has finished
-        let _ = download_stream
-            .buffer_unordered(MAX_CONCURRENT_DOWNLOADS)
-            .collect::<Vec<_>>()
-            .await;
-        drop(progress_tx);
-        monitor_handle.await.map_err(|err| err.to_string())?;
+        let failed_count = downloader
+            .download_task_items(
+                vec![
+                    (Arc::clone(&task1), download_options1),
+                    (Arc::clone(&task2), download_options2),
+                    (Arc::clone(&task3), download_options3),
+                ],
+                on_event,
+            )
+            .await
+            .map_err(|err| err.to_string())?;
+        if signal.is_cancelled() {
+            log::info!("task of downloading mc {} cancelled", version_id);
+            return Err(McApiError::Cancelled.to_string());
+        }
+        // the task stays in the queue to be resumed
+        if failed_count > 0 {
+            return Err(format!("{} files failed to download", failed_count));
+        }
         log::info!(
             "finished downloading {} files!",
             task1.lock().await.files.len()
@@ -593,3 +1062,97 @@ pub mod minecraft_resource {
         Ok(())
     }
 }
+
+#[cfg(test)]
+#[tokio::test]
+async fn restart_without_range_support() {
+    use crate::{
+        core::api_client::ApiProvider,
+        util::mock_server::{self, Response},
+    };
+    use sha1::Digest;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let body: Vec<u8> = (0..4 << 20).map(|i| (i % 251) as u8).collect();
+    let sha1 = format!("{:x}", sha1::Sha1::digest(&body));
+    let size = body.len();
+    let broken = AtomicBool::new(true);
+    // the range is ignored, and the first response breaks after 3 MB
+    let base = mock_server::serve(move |_| {
+        if broken.swap(false, Ordering::Relaxed) {
+            Response::new(200, &body[..3 << 20]).header("Content-Length", &size.to_string())
+        } else {
+            Response::new(200, body.clone())
+        }
+    })
+    .await;
+    let dir = std::env::temp_dir().join("pcl-proto-downloader-test");
+    let _ = fs::remove_dir_all(&dir);
+    let out_path = dir.join("file.bin");
+    let (progress_tx, mut progress_rx) = mpsc::channel(4096);
+    Downloader::new(&ApiBases::new(&ApiProvider::Official))
+        .start_download(
+            DownloadConfig {
+                file_index: 0,
+                out_path: out_path.clone(),
+                task_item_id: 0,
+                info: DownloadInfo {
+                    sha1,
+                    size: size as u64,
+                    url: format!("{base}/file.bin"),
+                    path: None,
+                },
+            },
+            progress_tx,
+        )
+        .await
+        .unwrap();
+    assert_eq!(fs::metadata(&out_path).unwrap().len(), size as u64);
+    fs::remove_dir_all(&dir).unwrap();
+    // the progress monitor requires a file to report pending before it goes back
+    let mut last = 0;
+    while let Ok(update) = progress_rx.try_recv() {
+        let progress = update.progress;
+        assert!(progress.downloaded_bytes >= last || progress.status == TaskStatus::Pending);
+        last = progress.downloaded_bytes;
+    }
+    assert_eq!(last, size as u64);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn cancel_while_fetching() {
+    use crate::core::{api_client::ApiProvider, task_manager::TaskManager};
+    use std::time::Duration;
+
+    // a server which never answers
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/index.json", listener.local_addr().unwrap());
+    let task_manager = Arc::new(TaskManager::default());
+    let task = task_manager.register(0);
+    let downloader =
+        Downloader::new(&ApiBases::new(&ApiProvider::Official)).with_signal(task.signal.clone());
+    let dir = std::env::temp_dir().join("pcl-proto-cancel-while-fetching-test");
+    let info = DownloadInfo {
+        sha1: "0".repeat(40),
+        size: 0,
+        url,
+        path: None,
+    };
+    // paused while fetching, then cancelled while paused
+    let controller = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        task_manager.pause(0).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        task_manager.cancel(0).unwrap();
+    });
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        downloader.download_without_report(&info, &dir),
+    )
+    .await
+    .unwrap();
+    controller.await.unwrap();
+    let _ = fs::remove_dir_all(&dir);
+    assert!(matches!(result, Err(McApiError::Cancelled)));
+}
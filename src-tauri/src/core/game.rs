@@ -1,16 +1,48 @@
-use std::{fs, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::core::{
     api_client::game::VersionDetails, java::JavaRuntime, launcher::GameLaunchError,
     mcmod::PluginType, repository::GameRepository,
 };
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub enum GameJava {
+    #[default]
     Default,
     Custom(Arc<JavaRuntime>),
 }
 
+/// which versions use their own folder as the game directory,
+/// so that their mods, saves and options are kept apart from other versions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionIsolation {
+    /// all versions share the global `.minecraft`
+    Never,
+    /// versions with a mod loader installed
+    ModdedOnly,
+    /// snapshots and other versions which are not a release
+    Snapshots,
+    #[default]
+    Always,
+}
+
+impl VersionIsolation {
+    pub fn isolates(&self, instance: &GameInstance) -> bool {
+        match self {
+            VersionIsolation::Never => false,
+            VersionIsolation::ModdedOnly => !matches!(instance.plugin_type, PluginType::Vanilla),
+            VersionIsolation::Snapshots => instance.version_type != "release",
+            VersionIsolation::Always => true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GameInstance {
     pub id: String,
@@ -20,9 +52,16 @@ pub struct GameInstance {
     pub version: String,
     pub json_path: PathBuf,
     pub natives_path: PathBuf,
-    pub game_java: GameJava,
     pub global_dir: PathBuf,
+    #[serde(default)]
     pub plugin_type: PluginType,
+    /// `type` in the version json, such as release or snapshot
+    #[serde(default = "default_version_type")]
+    pub version_type: String,
+}
+
+fn default_version_type() -> String {
+    "release".to_string()
 }
 
 #[derive(Debug)]
@@ -56,16 +95,22 @@ impl GameInstance {
             let id = json_content["id"]
                 .as_str()
                 .ok_or(GameInstanceError::InvalidVersionJson)?;
-            let jar_name = if let Some(jar_name_in_json) = json_content["jar"].as_str() {
-                jar_name_in_json // use jar name recorded in json file if there is
-            } else {
-                folder_name // use folder name as jar name if there is no jar name in json file
-            };
-            let jar_path = version_folder.join(format!("{}.jar", jar_name));
-            if !jar_path.exists() {
-                log::error!("jar file not found in folder: {:?}", jar_path);
-                return Err(GameInstanceError::InvalidVersionJar);
-            }
+            let versions_folder = repo.path.join("versions");
+            let ancestors = read_ancestors(&json_content, &versions_folder);
+            // modloader versions may reuse the jar of the version they inherit from
+            let jar_path = std::iter::once((version_folder.clone(), folder_name, &json_content))
+                .chain(ancestors.iter().filter_map(|parent| {
+                    let id = parent["id"].as_str()?;
+                    Some((versions_folder.join(id), id, parent))
+                }))
+                .flat_map(|(folder, name, json)| {
+                    jar_candidates(&folder, name, json, &versions_folder)
+                })
+                .find(|jar_path| jar_path.is_file())
+                .ok_or_else(|| {
+                    log::error!("jar file not found for version: {:?}", version_folder);
+                    GameInstanceError::InvalidVersionJar
+                })?;
             let version = {
                 let patches = json_content["patches"].as_array();
                 if let Some(patches) = patches {
@@ -79,7 +124,13 @@ impl GameInstance {
                     None
                 }
             };
-            let version = version.unwrap_or(id).to_string();
+            // the vanilla version at the root of the chain
+            let version = version
+                .or(ancestors.last().and_then(|root| root["id"].as_str()))
+                .or(json_content["inheritsFrom"].as_str())
+                .or(json_content["jar"].as_str())
+                .unwrap_or(id)
+                .to_string();
             return Ok(GameInstance {
                 id: id.to_string(),
                 name: folder_name.to_string(),
@@ -88,9 +139,11 @@ impl GameInstance {
                 version,
                 json_path,
                 natives_path: version_folder.join("natives"),
-                game_java: GameJava::Default,
                 global_dir: repo.path.clone(),
-                plugin_type: PluginType::default(),
+                plugin_type: PluginType::detect(&json_content),
+                version_type: json_content["type"]
+                    .as_str()
+                    .map_or_else(default_version_type, str::to_string),
             });
         } else {
             log::error!("version json not found in folder: {:?}", version_folder);
@@ -98,11 +151,198 @@ impl GameInstance {
         }
     }
 
+    /// the directory the game runs in, where mods, saves and options are.
+    /// the instance setting overrides the global isolation policy
+    pub fn game_directory(&self, policy: VersionIsolation, isolation: Option<bool>) -> PathBuf {
+        if isolation.unwrap_or_else(|| policy.isolates(self)) {
+            self.directory.clone()
+        } else {
+            self.global_dir.clone()
+        }
+    }
+
+    /// jar files in the mods folder, disabled ones included
+    pub fn list_mods(game_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let mods_dir = game_dir.join("mods");
+        if !mods_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut mods: Vec<PathBuf> = fs::read_dir(mods_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| {
+                            name.ends_with(".jar") || name.ends_with(".jar.disabled")
+                        })
+            })
+            .collect();
+        mods.sort();
+        Ok(mods)
+    }
+
+    /// world folders in the saves folder
+    pub fn list_saves(game_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let saves_dir = game_dir.join("saves");
+        if !saves_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut saves: Vec<PathBuf> = fs::read_dir(saves_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.join("level.dat").is_file())
+            .collect();
+        saves.sort();
+        Ok(saves)
+    }
+
+    /// read the version json, merged with all the versions it inherits from
     pub fn read_version_json(&self) -> Result<VersionDetails, GameLaunchError> {
         let json_reader = std::fs::File::open(&self.json_path)?;
-        let version_json: VersionDetails = serde_json::from_reader(json_reader)?;
-        Ok(version_json)
+        let version_json: serde_json::Value = serde_json::from_reader(json_reader)?;
+        let version_json = resolve_inheritance(version_json, &self.global_dir.join("versions"))?;
+        Ok(serde_json::from_value(version_json)?)
+    }
+}
+
+/// the jars a version may run with, in the order to look for them:
+/// its own, then the one of the version named by `jar`
+fn jar_candidates(
+    folder: &Path,
+    name: &str,
+    json: &serde_json::Value,
+    versions_folder: &Path,
+) -> Vec<PathBuf> {
+    let mut candidates = vec![folder.join(format!("{name}.jar"))];
+    if let Some(jar) = json["jar"].as_str() {
+        candidates.push(folder.join(format!("{jar}.jar")));
+        candidates.push(versions_folder.join(jar).join(format!("{jar}.jar")));
+    }
+    candidates
+}
+
+/// the jsons of the versions in the `inheritsFrom` chain, the nearest first,
+/// as far as they can be read
+fn read_ancestors(
+    version_json: &serde_json::Value,
+    versions_folder: &Path,
+) -> Vec<serde_json::Value> {
+    let mut ancestors: Vec<serde_json::Value> = Vec::new();
+    let mut visited: HashSet<String> = version_json["id"]
+        .as_str()
+        .map(str::to_string)
+        .into_iter()
+        .collect();
+    let mut parent_id = version_json["inheritsFrom"].as_str().map(str::to_string);
+    while let Some(id) = parent_id.take() {
+        if !visited.insert(id.clone()) {
+            break;
+        }
+        let Some(parent) = fs::File::open(versions_folder.join(&id).join(format!("{id}.json")))
+            .ok()
+            .and_then(|reader| serde_json::from_reader::<_, serde_json::Value>(reader).ok())
+        else {
+            break;
+        };
+        parent_id = parent["inheritsFrom"].as_str().map(str::to_string);
+        ancestors.push(parent);
     }
+    ancestors
+}
+
+/// walk the `inheritsFrom` chain in the versions folder and merge every parent into the child
+fn resolve_inheritance(
+    version_json: serde_json::Value,
+    versions_folder: &Path,
+) -> Result<serde_json::Value, GameLaunchError> {
+    let mut merged = version_json;
+    let mut visited = HashSet::new();
+    if let Some(id) = merged["id"].as_str() {
+        visited.insert(id.to_string());
+    }
+    while let Some(parent_id) = merged["inheritsFrom"].as_str().map(str::to_string) {
+        if !visited.insert(parent_id.clone()) {
+            return Err(GameLaunchError::InheritanceLoop(parent_id));
+        }
+        let parent_path = versions_folder
+            .join(&parent_id)
+            .join(format!("{}.json", parent_id));
+        let parent_reader = fs::File::open(&parent_path)
+            .map_err(|_| GameLaunchError::MissingParentVersion(parent_id.clone()))?;
+        let parent_json: serde_json::Value = serde_json::from_reader(parent_reader)?;
+        log::debug!("merging version json {} into {:?}", parent_id, merged["id"]);
+        merged = merge_version_json(parent_json, merged);
+    }
+    Ok(merged)
+}
+
+/// merge a child version json onto its parent.
+/// libraries and arguments are concatenated (child wins on the same library),
+/// other fields declared by the child override the parent's
+fn merge_version_json(parent: serde_json::Value, child: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+    let (Value::Object(mut merged), Value::Object(child)) = (parent, child) else {
+        return Value::Null;
+    };
+    for (key, child_value) in child {
+        match key.as_str() {
+            // the chain continues from the parent's inheritsFrom
+            "inheritsFrom" => continue,
+            "libraries" => {
+                let parent_libraries = merged.remove(&key).unwrap_or(Value::Array(Vec::new()));
+                merged.insert(key, merge_libraries(parent_libraries, child_value));
+            }
+            "arguments" => {
+                let parent_arguments = merged.remove(&key).unwrap_or(Value::Null);
+                merged.insert(key, merge_arguments(parent_arguments, child_value));
+            }
+            _ => {
+                merged.insert(key, child_value);
+            }
+        }
+    }
+    Value::Object(merged)
+}
+
+/// `group:artifact[:classifier]` of a library, the version is ignored
+fn library_key(library: &serde_json::Value) -> Option<String> {
+    let name = library["name"].as_str()?;
+    let mut parts = name.split(':');
+    let group = parts.next()?;
+    let artifact = parts.next()?;
+    let _version = parts.next();
+    Some(match parts.next() {
+        Some(classifier) => format!("{group}:{artifact}:{classifier}"),
+        None => format!("{group}:{artifact}"),
+    })
+}
+
+fn merge_libraries(parent: serde_json::Value, child: serde_json::Value) -> serde_json::Value {
+    let child_libraries = child.as_array().cloned().unwrap_or_default();
+    let child_keys: HashSet<String> = child_libraries.iter().filter_map(library_key).collect();
+    let mut libraries = child_libraries;
+    libraries.extend(
+        parent
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|library| library_key(library).is_none_or(|key| !child_keys.contains(&key))),
+    );
+    serde_json::Value::Array(libraries)
+}
+
+fn merge_arguments(parent: serde_json::Value, child: serde_json::Value) -> serde_json::Value {
+    let mut merged = serde_json::Map::new();
+    for kind in ["game", "jvm"] {
+        let mut values = parent[kind].as_array().cloned().unwrap_or_default();
+        values.extend(child[kind].as_array().cloned().unwrap_or_default());
+        merged.insert(kind.to_string(), serde_json::Value::Array(values));
+    }
+    serde_json::Value::Object(merged)
 }
 
 #[cfg(test)]
@@ -115,3 +355,163 @@ fn from_version_folder() {
     let instance = GameInstance::from_version_folder(&version_folder, &game_repo);
     println!("{:?}", instance);
 }
+
+#[cfg(test)]
+#[test]
+fn from_version_folder_chain() {
+    let repo_path = std::env::temp_dir().join("pcl-proto-version-chain-test");
+    let _ = fs::remove_dir_all(&repo_path);
+    let versions = [
+        serde_json::json!({ "id": "1.12.2" }),
+        serde_json::json!({ "id": "1.12.2-forge-14.23.5.2860", "inheritsFrom": "1.12.2" }),
+        serde_json::json!({
+            "id": "1.12.2-OptiFine_HD_U_G5",
+            "inheritsFrom": "1.12.2-forge-14.23.5.2860"
+        }),
+        // installed by hmcl without a jar of its own
+        serde_json::json!({ "id": "Forge 1.12.2", "jar": "1.12.2" }),
+    ];
+    for version in &versions {
+        let id = version["id"].as_str().unwrap();
+        let folder = repo_path.join("versions").join(id);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join(format!("{id}.json")), version.to_string()).unwrap();
+    }
+    let vanilla_jar = repo_path.join("versions/1.12.2/1.12.2.jar");
+    fs::write(&vanilla_jar, b"").unwrap();
+    let repo = GameRepository::new("test", repo_path.clone());
+    let optifine = GameInstance::from_version_folder(
+        &repo_path.join("versions/1.12.2-OptiFine_HD_U_G5"),
+        &repo,
+    );
+    let hmcl = GameInstance::from_version_folder(&repo_path.join("versions/Forge 1.12.2"), &repo);
+    fs::remove_dir_all(&repo_path).unwrap();
+    let optifine = optifine.unwrap();
+    assert_eq!(optifine.jar_path, vanilla_jar);
+    assert_eq!(optifine.version, "1.12.2");
+    let hmcl = hmcl.unwrap();
+    assert_eq!(hmcl.jar_path, vanilla_jar);
+    assert_eq!(hmcl.version, "1.12.2");
+}
+
+#[cfg(test)]
+#[test]
+fn version_isolation_test() {
+    let instance = GameInstance {
+        id: "1.21.8-fabric".to_string(),
+        name: "1.21.8-fabric".to_string(),
+        directory: PathBuf::from("/mc/versions/1.21.8-fabric"),
+        jar_path: PathBuf::from("/mc/versions/1.21.8/1.21.8.jar"),
+        version: "1.21.8".to_string(),
+        json_path: PathBuf::from("/mc/versions/1.21.8-fabric/1.21.8-fabric.json"),
+        natives_path: PathBuf::from("/mc/versions/1.21.8-fabric/natives"),
+        global_dir: PathBuf::from("/mc"),
+        plugin_type: PluginType::Fabric,
+        version_type: "release".to_string(),
+    };
+    let isolated = &instance.directory;
+    let shared = &instance.global_dir;
+    assert_eq!(
+        &instance.game_directory(VersionIsolation::Never, None),
+        shared
+    );
+    assert_eq!(
+        &instance.game_directory(VersionIsolation::ModdedOnly, None),
+        isolated
+    );
+    assert_eq!(
+        &instance.game_directory(VersionIsolation::Snapshots, None),
+        shared
+    );
+    assert_eq!(
+        &instance.game_directory(VersionIsolation::Always, Some(false)),
+        shared
+    );
+    assert_eq!(
+        &instance.game_directory(VersionIsolation::Never, Some(true)),
+        isolated
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn merge_version_json_test() {
+    let parent = serde_json::json!({
+        "id": "1.21.8",
+        "mainClass": "net.minecraft.client.main.Main",
+        "assets": "26",
+        "libraries": [
+            { "name": "org.ow2.asm:asm:9.3" },
+            { "name": "com.mojang:brigadier:1.3.10" }
+        ],
+        "arguments": { "game": ["--username", "${auth_player_name}"], "jvm": ["-cp", "${classpath}"] }
+    });
+    let child = serde_json::json!({
+        "id": "fabric-loader-0.17.2-1.21.8",
+        "inheritsFrom": "1.21.8",
+        "mainClass": "net.fabricmc.loader.impl.launch.knot.KnotClient",
+        "libraries": [{ "name": "org.ow2.asm:asm:9.8" }],
+        "arguments": { "jvm": ["-DFabricMcEmu= net.minecraft.client.main.Main "] }
+    });
+    let merged = merge_version_json(parent, child);
+    assert_eq!(merged["id"], "fabric-loader-0.17.2-1.21.8");
+    assert_eq!(
+        merged["mainClass"],
+        "net.fabricmc.loader.impl.launch.knot.KnotClient"
+    );
+    assert_eq!(merged["assets"], "26");
+    assert!(merged.get("inheritsFrom").is_none());
+    let library_names: Vec<&str> = merged["libraries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|library| library["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        library_names,
+        ["org.ow2.asm:asm:9.8", "com.mojang:brigadier:1.3.10"]
+    );
+    assert_eq!(merged["arguments"]["jvm"].as_array().unwrap().len(), 3);
+    assert_eq!(merged["arguments"]["game"].as_array().unwrap().len(), 2);
+
+    // optifine on forge on vanilla
+    let versions_folder = std::env::temp_dir().join("pcl-proto-merge-version-json-test");
+    let versions = [
+        serde_json::json!({
+            "id": "1.12.2",
+            "mainClass": "net.minecraft.client.main.Main",
+            "minecraftArguments": "--username ${auth_player_name}",
+            "libraries": [{ "name": "org.lwjgl.lwjgl:lwjgl:2.9.4-nightly-20150209" }]
+        }),
+        serde_json::json!({
+            "id": "1.12.2-forge-14.23.5.2860",
+            "inheritsFrom": "1.12.2",
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "minecraftArguments": "--username ${auth_player_name} --tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker",
+            "libraries": [{ "name": "net.minecraft:launchwrapper:1.12" }]
+        }),
+    ];
+    for version in &versions {
+        let id = version["id"].as_str().unwrap();
+        let folder = versions_folder.join(id);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join(format!("{id}.json")), version.to_string()).unwrap();
+    }
+    let optifine = serde_json::json!({
+        "id": "1.12.2-OptiFine_HD_U_G5",
+        "inheritsFrom": "1.12.2-forge-14.23.5.2860",
+        "libraries": [{ "name": "optifine:OptiFine:1.12.2_HD_U_G5" }]
+    });
+    let merged = resolve_inheritance(optifine, &versions_folder).unwrap();
+    fs::remove_dir_all(&versions_folder).unwrap();
+    assert_eq!(merged["id"], "1.12.2-OptiFine_HD_U_G5");
+    assert_eq!(merged["mainClass"], "net.minecraft.launchwrapper.Launch");
+    assert!(
+        merged["minecraftArguments"]
+            .as_str()
+            .unwrap()
+            .ends_with("FMLTweaker")
+    );
+    assert_eq!(merged["libraries"].as_array().unwrap().len(), 3);
+    assert!(merged.get("inheritsFrom").is_none());
+}
@@ -0,0 +1,92 @@
+//! Launch settings of a single game instance.
+//! Saved as `PCL/settings.json` in the version folder like the `PCL/Setup.ini` of PCL,
+//! fields not set fall back to the global setup.
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::core::game::GameJava;
+
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct InstanceSettings {
+    /// in MB
+    pub min_memory: Option<usize>,
+    /// in MB, falls back to the global max memory
+    pub max_memory: Option<usize>,
+    pub extra_jvm_args: Vec<String>,
+    pub extra_game_args: Vec<String>,
+    pub window_width: Option<usize>,
+    pub window_height: Option<usize>,
+    pub fullscreen: bool,
+    pub java: GameJava,
+    /// command to run java with, such as `gamemoderun` or `prime-run`
+    pub wrapper_command: Option<String>,
+    pub env: HashMap<String, String>,
+    /// whether the version folder is used as the game directory, overrides the global policy
+    pub version_isolation: Option<bool>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InstanceSettingsError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("instance settings corrupted: {0}")]
+    Corrupted(#[from] serde_json::Error),
+}
+
+impl InstanceSettings {
+    pub fn path(instance_dir: &Path) -> PathBuf {
+        instance_dir.join("PCL").join("settings.json")
+    }
+
+    /// load the settings of the instance, default settings if never saved
+    pub fn load(instance_dir: &Path) -> Result<Self, InstanceSettingsError> {
+        let path = Self::path(instance_dir);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let reader = std::io::BufReader::new(fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save(&self, instance_dir: &Path) -> Result<(), InstanceSettingsError> {
+        let path = Self::path(instance_dir);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = std::io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn instance_settings_round_trip() {
+    let instance_dir = std::env::temp_dir().join("pcl-proto-instance-settings-test");
+    let _ = fs::remove_dir_all(&instance_dir);
+    assert!(
+        InstanceSettings::load(&instance_dir)
+            .unwrap()
+            .max_memory
+            .is_none()
+    );
+    let settings = InstanceSettings {
+        max_memory: Some(6144),
+        extra_jvm_args: vec!["-XX:+UseZGC".to_string()],
+        env: HashMap::from([("MESA_GL_VERSION_OVERRIDE".to_string(), "4.5".to_string())]),
+        ..Default::default()
+    };
+    settings.save(&instance_dir).unwrap();
+    let loaded = InstanceSettings::load(&instance_dir).unwrap();
+    assert_eq!(loaded.max_memory, Some(6144));
+    assert_eq!(loaded.extra_jvm_args, ["-XX:+UseZGC"]);
+    assert_eq!(loaded.env["MESA_GL_VERSION_OVERRIDE"], "4.5");
+    fs::remove_dir_all(&instance_dir).unwrap();
+}
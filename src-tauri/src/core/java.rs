@@ -33,6 +33,24 @@ impl PartialEq for JavaRuntime {
     }
 }
 
+/// which kind of java runtime is preferred when both fit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JavaKindPolicy {
+    #[default]
+    Any,
+    PreferJre,
+    PreferJdk,
+}
+
+/// no installed java runtime can run the game
+#[derive(Debug, thiserror::Error)]
+#[error("no installed java fits java {required}, found: [{}]", found.join(", "))]
+pub struct NoSuitableJava {
+    pub required: i32,
+    pub found: Vec<String>,
+}
+
 #[derive(Debug)]
 pub enum JavaRuntimeConstructorError {
     MissingFile,
@@ -114,6 +132,66 @@ impl JavaRuntime {
         None
     }
 
+    /// whether the runtime is able to run a game requiring the java major version,
+    /// or exactly that version for the games which break on a newer java
+    pub fn fits(&self, required: i32, exact: bool) -> bool {
+        if matches!(self.compability, Compability::No) {
+            return false;
+        }
+        if exact {
+            self.slug_version == required
+        } else {
+            self.slug_version >= required
+        }
+    }
+
+    /// pick the best runtime for the required java major version.
+    /// the closest major version wins, then the better compability, 64-bit, and the kind policy
+    pub fn select<'a>(
+        runtimes: impl IntoIterator<Item = &'a JavaRuntime>,
+        required: i32,
+        exact: bool,
+        policy: JavaKindPolicy,
+    ) -> Result<&'a JavaRuntime, NoSuitableJava> {
+        let runtimes: Vec<&JavaRuntime> = runtimes.into_iter().collect();
+        runtimes
+            .iter()
+            .filter(|runtime| runtime.fits(required, exact))
+            .min_by_key(|runtime| {
+                let compability = match runtime.compability {
+                    Compability::Perfect => 0,
+                    Compability::Translation => 1,
+                    _ => 2,
+                };
+                let kind = match policy {
+                    JavaKindPolicy::Any => false,
+                    JavaKindPolicy::PreferJre => runtime.is_jdk,
+                    JavaKindPolicy::PreferJdk => !runtime.is_jdk,
+                };
+                (
+                    runtime.slug_version - required,
+                    compability,
+                    !runtime.is_64_bit,
+                    kind,
+                )
+            })
+            .copied()
+            .ok_or_else(|| NoSuitableJava {
+                required,
+                found: runtimes.iter().map(|runtime| runtime.describe()).collect(),
+            })
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{} ({:?}, {:?}) at {}",
+            self.version,
+            self.architecture,
+            self.compability,
+            self.java_exe.display()
+        )
+    }
+
     /// search java runtime in system
     pub async fn search() -> Vec<Self> {
         let mut collect_paths: HashSet<String> = HashSet::new();
@@ -142,10 +220,14 @@ impl JavaRuntime {
                 }
                 result
             }
-            collect_paths.extend(search_macos(Path::new("/Library/Java/JavaVirtualMachines")).await);
-            collect_paths.extend(search_macos(Path::new(
-                &home_dir.join("Library/Java/JavaVirtualMachines"),
-            )).await);
+            collect_paths
+                .extend(search_macos(Path::new("/Library/Java/JavaVirtualMachines")).await);
+            collect_paths.extend(
+                search_macos(Path::new(
+                    &home_dir.join("Library/Java/JavaVirtualMachines"),
+                ))
+                .await,
+            );
         }
         // search PATH
         {
@@ -304,6 +386,51 @@ mod tests {
         assert_eq!(java_runtime.version, "24.0.2");
     }
 
+    #[test]
+    fn select_java() {
+        use crate::core::platform::Architecture;
+        let runtime = |version: &str, slug_version, is_jdk, compability| JavaRuntime {
+            directory_path: PathBuf::from(format!("/java/{version}/bin")),
+            is_user_imported: false,
+            version: version.to_string(),
+            slug_version,
+            is_64_bit: true,
+            architecture: Architecture::X64,
+            compability,
+            is_jdk,
+            java_exe: PathBuf::from(format!("/java/{version}/bin/java")),
+            implementor: None,
+        };
+        let runtimes = [
+            runtime("1.8.0_362", 8, true, Compability::Perfect),
+            runtime("17.0.8", 17, true, Compability::Perfect),
+            runtime("21.0.2", 21, true, Compability::Translation),
+            runtime("21.0.4", 21, false, Compability::Perfect),
+            runtime("24.0.2", 24, true, Compability::Perfect),
+        ];
+        let select = |required, policy| {
+            JavaRuntime::select(&runtimes, required, false, policy)
+                .map(|runtime| runtime.version.as_str())
+        };
+        assert_eq!(select(8, JavaKindPolicy::Any).unwrap(), "1.8.0_362");
+        // a newer java runs the versions without launchwrapper
+        assert_eq!(
+            JavaRuntime::select(&runtimes[1..], 8, false, JavaKindPolicy::Any)
+                .unwrap()
+                .version,
+            "17.0.8"
+        );
+        assert!(JavaRuntime::select(&runtimes[1..], 8, true, JavaKindPolicy::Any).is_err());
+        assert!(runtimes[4].fits(8, false));
+        assert!(!runtimes[4].fits(8, true));
+        assert_eq!(select(16, JavaKindPolicy::Any).unwrap(), "17.0.8");
+        assert_eq!(select(21, JavaKindPolicy::PreferJdk).unwrap(), "21.0.4");
+        assert_eq!(select(22, JavaKindPolicy::Any).unwrap(), "24.0.2");
+        let error = select(25, JavaKindPolicy::Any).unwrap_err();
+        assert_eq!(error.required, 25);
+        assert_eq!(error.found.len(), 5);
+    }
+
     #[tokio::test]
     async fn java_search() {
         let start = Instant::now();
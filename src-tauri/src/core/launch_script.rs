@@ -0,0 +1,124 @@
+//! Rendering of a launch command into a script which can be run outside the launcher.
+//! The command comes from `LaunchOption`, so the script is the same as a real launch.
+use std::{ffi::OsStr, process::Command};
+
+/// what secrets are replaced with when masked
+const MASK: &str = "********";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScriptFormat {
+    /// POSIX shell script
+    Shell,
+    /// Windows batch file
+    Batch,
+}
+
+impl ScriptFormat {
+    /// the format of the current platform
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            ScriptFormat::Batch
+        } else {
+            ScriptFormat::Shell
+        }
+    }
+}
+
+/// render the command, every secret appearing in it is replaced with the mask
+pub fn render(command: &Command, format: ScriptFormat, secrets: &[&str]) -> String {
+    let mask = |value: &OsStr| {
+        let mut value = value.to_string_lossy().into_owned();
+        for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+            value = value.replace(secret, MASK);
+        }
+        value
+    };
+    let quote = match format {
+        ScriptFormat::Shell => quote_shell,
+        ScriptFormat::Batch => quote_batch,
+    };
+    let mut script = String::new();
+    match format {
+        ScriptFormat::Shell => script.push_str("#!/bin/sh\n"),
+        // paths may contain characters out of the code page
+        ScriptFormat::Batch => script.push_str("@echo off\r\nchcp 65001 >nul\r\n"),
+    }
+    let newline = match format {
+        ScriptFormat::Shell => "\n",
+        ScriptFormat::Batch => "\r\n",
+    };
+    if let Some(dir) = command.get_current_dir() {
+        let dir = quote(&mask(dir.as_os_str()));
+        match format {
+            ScriptFormat::Shell => script.push_str(&format!("cd {} || exit 1", dir)),
+            ScriptFormat::Batch => script.push_str(&format!("cd /d {}", dir)),
+        }
+        script.push_str(newline);
+    }
+    for (key, value) in command.get_envs() {
+        let key = key.to_string_lossy();
+        match (format, value) {
+            (ScriptFormat::Shell, Some(value)) => {
+                script.push_str(&format!("export {}={}", key, quote(&mask(value))))
+            }
+            (ScriptFormat::Shell, None) => script.push_str(&format!("unset {}", key)),
+            (ScriptFormat::Batch, value) => script.push_str(&format!(
+                "set \"{}={}\"",
+                key,
+                escape_batch(&value.map(mask).unwrap_or_default())
+            )),
+        }
+        script.push_str(newline);
+    }
+    let mut line = vec![quote(&mask(command.get_program()))];
+    if format == ScriptFormat::Shell {
+        line[0].insert_str(0, "exec ");
+    }
+    line.extend(command.get_args().map(|arg| quote(&mask(arg))));
+    let continuation = match format {
+        ScriptFormat::Shell => " \\\n  ",
+        ScriptFormat::Batch => " ^\r\n  ",
+    };
+    script.push_str(&line.join(continuation));
+    script.push_str(newline);
+    script
+}
+
+/// single quote everything, a single quote itself is closed, escaped and reopened
+fn quote_shell(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// double quote everything, the quotes inside are escaped for the argument parser of java
+fn quote_batch(value: &str) -> String {
+    format!("\"{}\"", escape_batch(&value.replace('"', "\\\"")))
+}
+
+/// `%` expands variables even in quotes
+fn escape_batch(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+#[cfg(test)]
+#[test]
+fn render_script_test() {
+    let mut command = Command::new("/usr/bin/java");
+    command
+        .args([
+            "-Xmx2048m",
+            "-Dname=Steve's world",
+            "--accessToken",
+            "secret",
+        ])
+        .current_dir("/mc")
+        .env("LANG", "en_US.UTF-8");
+    let shell = render(&command, ScriptFormat::Shell, &["secret"]);
+    assert_eq!(
+        shell,
+        "#!/bin/sh\ncd '/mc' || exit 1\nexport LANG='en_US.UTF-8'\nexec '/usr/bin/java' \\\n  '-Xmx2048m' \\\n  '-Dname=Steve'\\''s world' \\\n  '--accessToken' \\\n  '********'\n"
+    );
+    let batch = render(&command, ScriptFormat::Batch, &[]);
+    assert!(batch.contains("set \"LANG=en_US.UTF-8\"\r\n"));
+    assert!(batch.contains("\"--accessToken\" ^\r\n  \"secret\"\r\n"));
+}
@@ -9,17 +9,41 @@
 
 use serde::de::Error;
 
-use crate::core::api_client::game::VersionDetails;
+use crate::core::api_client::{
+    McApiError,
+    game::{AssetIndex, DownloadInfo, LaunchFeatures, LibraryItem, VersionDetails},
+};
+use crate::core::downloader::{Downloader, TaskItem, TaskItemReport, TaskStatus};
 use crate::setup::constants::{APP_VERSION, LAUNCHER_NAME};
+use crate::util::file;
 use crate::{
     core::{
         auth::Account,
+        authlib_injector::{self, AuthlibInjectorError},
         game::{GameInstance, GameJava},
-        java::JavaRuntime,
+        instance_settings::{InstanceSettings, InstanceSettingsError},
+        java::{JavaRuntime, NoSuitableJava},
+        launch_script::{self, ScriptFormat},
+        task_manager::TaskSignal,
     },
     setup::AppState,
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
+use tauri::ipc::Channel;
+
+const CLASSPATH_SEPARATOR: &str = if cfg!(target_os = "windows") {
+    ";"
+} else {
+    ":"
+};
+/// jvm arguments applied to versions whose json has no classpath in `arguments.jvm`
+const LEGACY_JVM_ARGUMENTS: [&str; 5] = [
+    "-Djava.library.path=${natives_directory}",
+    "-Dminecraft.launcher.brand=${launcher_name}",
+    "-Dminecraft.launcher.version=${launcher_version}",
+    "-cp",
+    "${classpath}",
+];
 
 /// Essential options for launching a Minecraft game
 pub struct LaunchOption {
@@ -30,6 +54,9 @@ pub struct LaunchOption {
     width: Option<usize>,
     height: Option<usize>,
     version_details: VersionDetails,
+    settings: InstanceSettings,
+    /// where the game runs, decided by version isolation
+    game_dir: std::path::PathBuf,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -40,45 +67,384 @@ pub enum GameLaunchError {
     #[error("Json parsing failed: {0}")]
     JsonError(#[from] serde_json::Error),
 
+    #[error("Zip extracting failed: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error(transparent)]
+    AuthlibInjectorError(#[from] AuthlibInjectorError),
+
+    #[error("authlib-injector not found: {0}")]
+    MissingAuthlibInjector(std::path::PathBuf),
+
+    #[error("library not found: {0}")]
+    MissingLibrary(std::path::PathBuf),
+
+    #[error("download failed: {0}")]
+    DownloadError(#[from] McApiError),
+
+    #[error("{0} files are still missing or corrupted after repairing")]
+    RepairFailed(usize),
+
+    #[error("background task failed: {0}")]
+    TaskJoinError(#[from] tokio::task::JoinError),
+
+    #[error("progress report failed: {0}")]
+    ReportError(#[from] tauri::Error),
+
     #[error("active java runtime not found")]
     MissingJava,
 
     #[error("active account not found")]
     MissingAccount,
 
+    #[error("the login of {0} has expired, log in again")]
+    AccountExpired(String),
+
     #[error("active game instance not found")]
     MissingGameInstance,
+
+    #[error(transparent)]
+    NoSuitableJava(#[from] NoSuitableJava),
+
+    #[error("instance settings: {0}")]
+    SettingsError(#[from] InstanceSettingsError),
+
+    #[error("parent version {0} inherited from is not installed")]
+    MissingParentVersion(String),
+
+    #[error("version {0} inherits from itself")]
+    InheritanceLoop(String),
 }
 
 impl LaunchOption {
+    pub fn game_instance(&self) -> &GameInstance {
+        &self.game_instance
+    }
+
+    pub fn game_dir(&self) -> &std::path::Path {
+        &self.game_dir
+    }
+
     pub fn with_window_size(mut self, width: usize, height: usize) -> Self {
         self.width = Some(width);
         self.height = Some(height);
         self
     }
 
-    pub fn launch_checked(&self) -> Result<std::process::Child, GameLaunchError> {
+    /// check and repair the game files before launching,
+    /// the progress of repairing is reported through the channel and it is paused and
+    /// cancelled by the signal of the task
+    pub async fn launch_checked(
+        &self,
+        on_event: Channel<TaskItemReport>,
+        task_id: i32,
+        signal: &TaskSignal,
+    ) -> Result<std::process::Child, GameLaunchError> {
         // check account
-        // check all the libraries
-        Ok(self.launch()?)
+        if matches!(self.account.as_ref(), Account::AuthlibInjector { .. }) {
+            authlib_injector::ensure_jar().await?;
+        }
+        self.repair_files(on_event, task_id, signal).await?;
+        self.extract_natives()?;
+        self.launch()
+    }
+
+    /// verify the client jar, libraries, asset index and asset objects by sha1,
+    /// then download the missing or corrupted ones.
+    /// the task items are the same as downloading a version: index, jar, libraries, assets
+    async fn repair_files(
+        &self,
+        on_event: Channel<TaskItemReport>,
+        task_id: i32,
+        signal: &TaskSignal,
+    ) -> Result<(), GameLaunchError> {
+        let downloader = Downloader::from_config().await.with_signal(signal.clone());
+        let global_dir = &self.game_instance.global_dir;
+        let completed_report = |item_id| TaskItemReport {
+            task_id,
+            item_id,
+            files_remaining: 0,
+            progress: 1.0,
+            status: TaskStatus::Completed,
+            speed: None,
+        };
+
+        // the asset index is needed to know what asset objects there are
+        let indexes_dir = global_dir.join("assets/indexes");
+        let mut asset_index_info = self.version_details.asset_index.clone();
+        asset_index_info.path = Some(format!("{}.json", self.version_details.assets));
+        let asset_index_path = indexes_dir.join(format!("{}.json", self.version_details.assets));
+        if !matches!(
+            file::check_sha1(&asset_index_path, &asset_index_info.sha1),
+            Ok(true)
+        ) {
+            log::warn!("asset index corrupted, downloading {:?}", asset_index_path);
+            downloader
+                .download_without_report(&asset_index_info, &indexes_dir)
+                .await?;
+        }
+        on_event.send(completed_report(0))?;
+        let asset_index: AssetIndex =
+            serde_json::from_reader(std::fs::File::open(&asset_index_path)?)?;
+
+        let jar_path = &self.game_instance.jar_path;
+        let mut jar_info = self.version_details.downloads.client.clone();
+        jar_info.path = jar_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        let libraries = self
+            .version_details
+            .libraries
+            .iter()
+            .filter(|lib| lib.rule_allow())
+            .flat_map(|lib| lib.downloads_for_current())
+            .filter(|info| info.path.is_some())
+            .collect();
+        let assets = asset_index
+            .objects
+            .values()
+            .map(|object| object.download_info())
+            .collect::<Result<_, _>>()?;
+        let candidates = vec![
+            (
+                1,
+                "jar",
+                vec![jar_info],
+                jar_path.parent().unwrap_or(global_dir).to_path_buf(),
+            ),
+            (2, "libraries", libraries, global_dir.join("libraries")),
+            (3, "assets", assets, global_dir.join("assets/objects")),
+        ];
+        // hashing thousands of files should not block the async runtime
+        let broken_groups = tokio::task::spawn_blocking(move || {
+            candidates
+                .into_iter()
+                .map(|(item_id, name, infos, out_dir)| {
+                    let broken: Vec<DownloadInfo> = infos
+                        .into_iter()
+                        .filter(|info| {
+                            let path = out_dir.join(info.path.as_deref().unwrap_or_default());
+                            !matches!(file::check_sha1(&path, &info.sha1), Ok(true))
+                        })
+                        .collect();
+                    (item_id, name, broken, out_dir)
+                })
+                .collect::<Vec<_>>()
+        })
+        .await?;
+
+        let mut task_items = Vec::new();
+        for (item_id, name, broken, out_dir) in broken_groups {
+            if broken.is_empty() {
+                on_event.send(completed_report(item_id))?;
+            } else {
+                log::warn!(
+                    "{} {} files missing or corrupted, repairing",
+                    broken.len(),
+                    name
+                );
+                task_items.push(TaskItem::build_with_infos(
+                    item_id, task_id, name, broken, out_dir,
+                ));
+            }
+        }
+        if task_items.is_empty() {
+            return Ok(());
+        }
+        let failed_count = downloader.download_task_items(task_items, on_event).await?;
+        if signal.is_cancelled() {
+            return Err(McApiError::Cancelled.into());
+        }
+        if failed_count > 0 {
+            return Err(GameLaunchError::RepairFailed(failed_count));
+        }
+        Ok(())
+    }
+
+    /// extract old-style natives jars into the natives folder,
+    /// skipped if the same jars have been extracted before
+    fn extract_natives(&self) -> Result<(), GameLaunchError> {
+        const MARKER_FILE: &str = ".pcl-natives";
+        let natives_path = &self.game_instance.natives_path;
+        let libraries_path = self.game_instance.global_dir.join("libraries");
+        let natives: Vec<_> = self
+            .version_details
+            .libraries
+            .iter()
+            .filter(|lib| lib.rule_allow())
+            .filter_map(|lib| Some((lib, lib.native_download()?)))
+            .collect();
+        if natives.is_empty() {
+            return Ok(());
+        }
+        let marker = natives
+            .iter()
+            .map(|(_, info)| info.sha1.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let marker_path = natives_path.join(MARKER_FILE);
+        if std::fs::read_to_string(&marker_path).is_ok_and(|content| content == marker) {
+            log::debug!("natives up to date at {:?}", natives_path);
+            return Ok(());
+        }
+        if natives_path.exists() {
+            std::fs::remove_dir_all(natives_path)?;
+        }
+        std::fs::create_dir_all(natives_path)?;
+        for (lib, info) in natives {
+            let jar_path = libraries_path.join(
+                info.path
+                    .as_deref()
+                    .ok_or(serde_json::Error::custom("Missing path in classifier"))?,
+            );
+            if !jar_path.exists() {
+                return Err(GameLaunchError::MissingLibrary(jar_path));
+            }
+            let exclude = lib
+                .extract
+                .as_ref()
+                .map(|extract| extract.exclude.as_slice())
+                .unwrap_or_default();
+            log::debug!("extracting natives {:?}", jar_path);
+            file::extract_zip(&jar_path, natives_path, exclude)?;
+        }
+        std::fs::write(marker_path, marker)?;
+        Ok(())
     }
 
     /// Launch a Minecraft game with the given options
-    fn launch(&self) -> Result<std::process::Child, std::io::Error> {
-        let mut command = std::process::Command::new(&self.java_runtime.java_exe);
+    fn launch(&self) -> Result<std::process::Child, GameLaunchError> {
+        if matches!(self.account.as_ref(), Account::AuthlibInjector { .. })
+            && !authlib_injector::jar_path().is_file()
+        {
+            return Err(GameLaunchError::MissingAuthlibInjector(
+                authlib_injector::jar_path(),
+            ));
+        }
+        let mut command = self.build_command()?;
+        command
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+        Ok(command.spawn()?)
+    }
+
+    /// render the command of this launch into a script, with the access token masked if asked
+    pub fn export_script(
+        &self,
+        format: ScriptFormat,
+        mask_tokens: bool,
+    ) -> Result<String, GameLaunchError> {
+        let command = self.build_command()?;
+        let secrets: Vec<&str> = match self.account.access_token() {
+            Some(access_token) if mask_tokens => vec![access_token],
+            _ => Vec::new(),
+        };
+        Ok(launch_script::render(&command, format, &secrets))
+    }
+
+    /// the command to run the game, java is run through the wrapper command if there is
+    fn build_command(&self) -> Result<std::process::Command, GameLaunchError> {
+        let classpath = self.build_classpath()?;
+        let placeholders = self.build_placeholders(&classpath);
+        let mut wrapper = self
+            .settings
+            .wrapper_command
+            .as_deref()
+            .unwrap_or_default()
+            .split_whitespace();
+        let mut command = match wrapper.next() {
+            Some(program) => {
+                let mut command = std::process::Command::new(program);
+                command.args(wrapper).arg(&self.java_runtime.java_exe);
+                command
+            }
+            None => std::process::Command::new(&self.java_runtime.java_exe),
+        };
         command
-            .args(self.build_jvm_arguments()) // build jvm arguments
-            .arg("-cp")
-            .arg(self.build_classpath().unwrap_or_default())
+            .args(self.build_jvm_arguments(&placeholders)) // build jvm arguments
             .arg(&self.version_details.main_class)
-            .args(self.build_game_arguments())
-            .current_dir(&self.game_instance.global_dir);
-        command.spawn()
+            .args(self.build_game_arguments(&placeholders))
+            .current_dir(&self.game_dir)
+            .envs(&self.settings.env);
+        Ok(command)
+    }
+
+    /// features used to evaluate the rules of arguments
+    fn launch_features(&self) -> LaunchFeatures {
+        LaunchFeatures {
+            has_custom_resolution: true,
+            ..Default::default()
+        }
+    }
+
+    /// values of the `${...}` placeholders in version json arguments
+    fn build_placeholders(&self, classpath: &str) -> HashMap<&'static str, String> {
+        let global_dir = &self.game_instance.global_dir;
+        let assets_root = global_dir.join("assets");
+        let game_assets = match self.version_details.assets.as_str() {
+            "legacy" | "pre-1.6" => assets_root.join("virtual").join("legacy"),
+            _ => assets_root.clone(),
+        };
+        // the uuid without hyphens stands for the token of offline accounts, as the game needs one
+        let access_token = match self.account.access_token() {
+            Some(access_token) => access_token.to_string(),
+            None => self.account.uuid().replace('-', ""),
+        };
+        HashMap::from([
+            ("auth_player_name", self.account.username().to_string()),
+            ("auth_uuid", self.account.uuid().to_string()),
+            (
+                "auth_session",
+                format!("token:{}:{}", access_token, self.account.uuid()),
+            ),
+            ("auth_access_token", access_token),
+            (
+                "auth_xuid",
+                self.account.xuid().unwrap_or_default().to_string(),
+            ),
+            (
+                "clientid",
+                self.account.client_id().unwrap_or_default().to_string(),
+            ),
+            ("user_type", self.account.user_type().to_string()),
+            ("user_properties", self.account.user_properties()),
+            ("version_name", self.game_instance.version.clone()),
+            ("version_type", LAUNCHER_NAME.to_string()),
+            ("game_directory", self.game_dir.display().to_string()),
+            ("assets_root", assets_root.display().to_string()),
+            ("game_assets", game_assets.display().to_string()),
+            ("assets_index_name", self.version_details.assets.clone()),
+            ("resolution_width", self.width.unwrap_or(854).to_string()),
+            ("resolution_height", self.height.unwrap_or(480).to_string()),
+            (
+                "natives_directory",
+                self.game_instance.natives_path.display().to_string(),
+            ),
+            ("launcher_name", LAUNCHER_NAME.to_string()),
+            ("launcher_version", APP_VERSION.to_string()),
+            ("classpath", classpath.to_string()),
+            ("classpath_separator", CLASSPATH_SEPARATOR.to_string()),
+            (
+                "library_directory",
+                global_dir.join("libraries").display().to_string(),
+            ),
+            (
+                "primary_jar_name",
+                self.game_instance
+                    .jar_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+        ])
     }
 
-    fn build_jvm_arguments(&self) -> Vec<String> {
+    fn build_jvm_arguments(&self, placeholders: &HashMap<&'static str, String>) -> Vec<String> {
         let mut args = Vec::new();
         // memory setting
+        if let Some(min_memory) = self.settings.min_memory {
+            args.push(format!("-Xms{}m", min_memory));
+        }
         args.push(format!("-Xmx{}m", self.max_memory));
         // encoding settings
         args.push("-Dfile.encoding=UTF-8".to_string());
@@ -103,22 +469,17 @@ impl LaunchOption {
         // macOS specific settings
         #[cfg(target_os = "macos")]
         {
-            args.push("-XstartOnFirstThread".to_string());
             args.push("-Xdock:name=Minecraft".to_string());
             // TODO: 图标路径需要从 assets 中获取
         }
         // native libraries path
         let natives_path = &self.game_instance.natives_path.display();
-        args.push(format!("-Djava.library.path={}", natives_path));
         args.push(format!("-Djna.tmpdir={}", natives_path));
         args.push(format!(
             "-Dorg.lwjgl.system.SharedLibraryExtractPath={}",
             natives_path
         ));
         args.push(format!("-Dio.netty.native.workdir={}", natives_path));
-        // launcher info
-        args.push(format!("-Dminecraft.launcher.brand={}", LAUNCHER_NAME));
-        args.push(format!("-Dminecraft.launcher.version={}", APP_VERSION));
         // gc optimize
         args.append(
             &mut [
@@ -135,25 +496,65 @@ impl LaunchOption {
             .map(|s| s.to_string())
             .collect(),
         );
+        // third-party authentication
+        if let Account::AuthlibInjector {
+            api_location,
+            server_meta,
+            ..
+        } = self.account.as_ref()
+        {
+            args.extend(authlib_injector::jvm_arguments(
+                &authlib_injector::jar_path(),
+                api_location,
+                server_meta,
+            ));
+        }
+        // extra arguments set by user
+        args.extend(self.settings.extra_jvm_args.iter().cloned());
+        // arguments declared by the version json, including library path, launcher info and classpath
+        let features = self.launch_features();
+        let declared: Vec<&str> = self
+            .version_details
+            .arguments
+            .iter()
+            .flat_map(|arguments| &arguments.jvm)
+            .flat_map(|argument| argument.values(&features))
+            .collect();
+        args.extend(
+            declared
+                .iter()
+                .map(|value| substitute_placeholders(value, placeholders)),
+        );
+        // versions before 17w43a don't declare jvm arguments,
+        // and the loaders inheriting from them declare only their own
+        if !declared.iter().any(|value| value.contains("${classpath}")) {
+            args.extend(
+                LEGACY_JVM_ARGUMENTS
+                    .iter()
+                    .map(|value| substitute_placeholders(value, placeholders)),
+            );
+        }
+        #[cfg(target_os = "macos")]
+        if !args.iter().any(|arg| arg == "-XstartOnFirstThread") {
+            args.insert(0, "-XstartOnFirstThread".to_string());
+        }
         args
     }
 
     fn build_classpath(&self) -> Result<String, GameLaunchError> {
-        let mut classpath = Vec::new();
-        let libraries = &self.version_details.libraries;
-        for lib in libraries {
-            let lib_artifact = &lib.downloads.artifact;
-            let lib_path = lib_artifact
-                .path
-                .as_deref()
-                .ok_or(serde_json::Error::custom("Missing path in artifact"))?;
-            let lib_full_path = format!(
-                "{}/libraries/{}",
-                self.game_instance.global_dir.display(),
+        let libraries_dir = self.game_instance.global_dir.join("libraries");
+        let mut classpath: Vec<String> = dedup_libraries(&self.version_details.libraries)
+            .into_iter()
+            // natives-only libraries are extracted rather than put on classpath
+            .filter_map(|lib| lib.artifact_path())
+            .map(|lib_path| {
                 lib_path
-            );
-            classpath.push(lib_full_path);
-        }
+                    .split('/')
+                    .fold(libraries_dir.clone(), |path, part| path.join(part))
+                    .display()
+                    .to_string()
+            })
+            .collect();
         if self.game_instance.jar_path.exists() {
             classpath.push(self.game_instance.jar_path.display().to_string());
         } else {
@@ -161,62 +562,200 @@ impl LaunchOption {
                 std::io::Error::new(std::io::ErrorKind::NotFound, "main jar not found").into(),
             );
         }
-        Ok(classpath.join(":"))
+        Ok(classpath.join(CLASSPATH_SEPARATOR))
     }
 
-    fn build_game_arguments(&self) -> Vec<String> {
-        vec![
-            format!("--username={}", (self.account.username())),
-            format!("--version={}", self.game_instance.version),
-            format!("--gameDir={}", self.game_instance.directory.display()),
-            format!(
-                "--assetsDir={}/assets",
-                self.game_instance.global_dir.display()
-            ),
-            format!("--assetIndex={}", &self.version_details.assets),
-            format!("--uuid={}", self.account.uuid()),
-            format!(
-                "--accessToken={}",
-                self.account.access_token().unwrap_or("0")
-            ),
-            format!("--userType={}", "msa"),
-            format!("--versionType={}", LAUNCHER_NAME),
-            format!("--width={}", self.width.unwrap_or(854)),
-            format!("--height={}", self.height.unwrap_or(480)),
-        ]
+    fn build_game_arguments(&self, placeholders: &HashMap<&'static str, String>) -> Vec<String> {
+        let features = self.launch_features();
+        let mut args = Vec::new();
+        // a loader declaring no game arguments of its own inherits `minecraftArguments`
+        if let Some(arguments) = self
+            .version_details
+            .arguments
+            .as_ref()
+            .filter(|arguments| !arguments.game.is_empty())
+        {
+            for argument in &arguments.game {
+                args.extend(
+                    argument
+                        .values(&features)
+                        .into_iter()
+                        .map(|value| substitute_placeholders(value, placeholders)),
+                );
+            }
+        } else if let Some(minecraft_arguments) = &self.version_details.minecraft_arguments {
+            args.extend(
+                minecraft_arguments
+                    .split_whitespace()
+                    .map(|value| substitute_placeholders(value, placeholders)),
+            );
+            // legacy versions have no rule for custom resolution
+            if features.has_custom_resolution {
+                args.extend(
+                    [
+                        "--width",
+                        "${resolution_width}",
+                        "--height",
+                        "${resolution_height}",
+                    ]
+                    .iter()
+                    .map(|value| substitute_placeholders(value, placeholders)),
+                );
+            }
+        }
+        if self.settings.fullscreen {
+            args.push("--fullscreen".to_string());
+        }
+        // extra arguments set by user
+        args.extend(self.settings.extra_game_args.iter().cloned());
+        args
     }
 
     /// build a launch option from app state if it is possible
     pub fn from_state(state: &AppState) -> Result<Self, GameLaunchError> {
         if let Some(game_instance) = state.active_game_instance.as_ref() {
-            let java_selected: &Arc<JavaRuntime> = match game_instance.game_java {
-                GameJava::Default => {
-                    if let Some(java_runtime) = state.pcl_setup_info.default_java.as_ref() {
-                        java_runtime
-                    } else {
+            let settings = InstanceSettings::load(&game_instance.directory)?;
+            let version_details = game_instance.read_version_json()?;
+            let required_java = version_details.required_java();
+            let exact_java = version_details.requires_exact_java();
+            let java_selected: Arc<JavaRuntime> = match settings.java {
+                GameJava::Default => match state.pcl_setup_info.default_java.as_ref() {
+                    // the default java set by user goes first if it fits
+                    Some(java_runtime) if java_runtime.fits(required_java, exact_java) => {
+                        java_runtime.clone()
+                    }
+                    _ if state.java_runtimes.is_empty() => {
                         return Err(GameLaunchError::MissingJava);
                     }
+                    _ => Arc::new(
+                        JavaRuntime::select(
+                            &state.java_runtimes,
+                            required_java,
+                            exact_java,
+                            state.pcl_setup_info.java_kind_policy,
+                        )?
+                        .clone(),
+                    ),
+                },
+                GameJava::Custom(ref java_runtime) => {
+                    if !java_runtime.fits(required_java, exact_java) {
+                        log::warn!(
+                            "custom java {:?} may not run java {} game",
+                            java_runtime.java_exe,
+                            required_java
+                        );
+                    }
+                    java_runtime.clone()
                 }
-                GameJava::Custom(ref java_runtime) => java_runtime,
             };
+            log::info!(
+                "java {:?} selected for java {} game",
+                java_selected.java_exe,
+                required_java
+            );
             let active_account = state.active_account.as_ref();
             if active_account.is_none() {
                 return Err(GameLaunchError::MissingAccount);
             }
+            if active_account.unwrap().is_expired() {
+                return Err(GameLaunchError::AccountExpired(
+                    active_account.unwrap().username().to_string(),
+                ));
+            }
             return Ok(Self {
                 account: active_account.unwrap().clone(),
-                java_runtime: java_selected.clone(),
+                java_runtime: java_selected,
                 game_instance: game_instance.clone(),
-                max_memory: state.pcl_setup_info.max_memory,
-                width: None,
-                height: None,
-                version_details: game_instance.read_version_json()?,
+                max_memory: settings
+                    .max_memory
+                    .unwrap_or(state.pcl_setup_info.max_memory),
+                width: settings.window_width,
+                height: settings.window_height,
+                version_details,
+                game_dir: game_instance.game_directory(
+                    state.pcl_setup_info.version_isolation,
+                    settings.version_isolation,
+                ),
+                settings,
             });
         }
         Err(GameLaunchError::MissingGameInstance)
     }
 }
 
+/// libraries allowed on current os, only the newest version is kept among the same group:artifact.
+/// the position of the first one is kept, as mod loaders put their libraries first
+fn dedup_libraries(libraries: &[LibraryItem]) -> Vec<&LibraryItem> {
+    let mut deduped: Vec<&LibraryItem> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for lib in libraries.iter().filter(|lib| lib.rule_allow()) {
+        match positions.get(&lib.maven_key()) {
+            Some(&position) => {
+                let kept = deduped[position];
+                if compare_versions(lib.maven_version(), kept.maven_version()).is_gt() {
+                    log::debug!("library {} replaced by {}", kept.name, lib.name);
+                    deduped[position] = lib;
+                }
+            }
+            None => {
+                positions.insert(lib.maven_key(), deduped.len());
+                deduped.push(lib);
+            }
+        }
+    }
+    deduped
+}
+
+/// compare versions like `9.8` and `9.3.1-beta`, numeric parts are compared as numbers
+fn compare_versions(a: Option<&str>, b: Option<&str>) -> std::cmp::Ordering {
+    let split = |version: Option<&str>| -> Vec<String> {
+        version
+            .unwrap_or_default()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+    for (a, b) in a.iter().zip(&b) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// replace every `${name}` in the argument with its value,
+/// unknown placeholders are kept as they are
+fn substitute_placeholders(argument: &str, placeholders: &HashMap<&'static str, String>) -> String {
+    let mut result = String::with_capacity(argument.len());
+    let mut rest = argument;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                match placeholders.get(name) {
+                    Some(value) => result.push_str(value),
+                    None => result.push_str(&rest[start..start + 2 + end + 1]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
 #[cfg(test)]
 #[test]
 pub fn game_launch_test() {
@@ -242,6 +781,8 @@ pub fn game_launch_test() {
         width: None,
         height: None,
         version_details: game_instance.read_version_json().unwrap(),
+        settings: InstanceSettings::default(),
+        game_dir: game_instance.directory.clone(),
     }
     .with_window_size(1280, 720);
     if let Ok(mut child) = launch_option.launch() {
@@ -253,3 +794,65 @@ pub fn game_launch_test() {
         );
     }
 }
+
+#[cfg(test)]
+#[test]
+fn dedup_libraries_test() {
+    use std::cmp::Ordering;
+    assert_eq!(
+        compare_versions(Some("9.8"), Some("9.3.1")),
+        Ordering::Greater
+    );
+    assert_eq!(
+        compare_versions(Some("33.3.1-jre"), Some("32.1.2-jre")),
+        Ordering::Greater
+    );
+    assert_eq!(
+        compare_versions(Some("1.10"), Some("1.9")),
+        Ordering::Greater
+    );
+    assert_eq!(compare_versions(Some("2.0"), Some("2.0.0")), Ordering::Less);
+    let libraries: Vec<LibraryItem> = serde_json::from_value(serde_json::json!([
+        { "name": "org.ow2.asm:asm:9.3" },
+        { "name": "com.google.guava:guava:33.3.1-jre" },
+        { "name": "org.ow2.asm:asm:9.8" },
+        { "name": "org.lwjgl:lwjgl:3.3.3" },
+        { "name": "org.lwjgl:lwjgl:3.3.3:natives-linux" },
+        { "name": "com.google.guava:guava:32.1.2-jre" }
+    ]))
+    .unwrap();
+    let names: Vec<&str> = dedup_libraries(&libraries)
+        .iter()
+        .map(|lib| lib.name.as_str())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "org.ow2.asm:asm:9.8",
+            "com.google.guava:guava:33.3.1-jre",
+            "org.lwjgl:lwjgl:3.3.3",
+            "org.lwjgl:lwjgl:3.3.3:natives-linux"
+        ]
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn substitute_placeholders_test() {
+    let placeholders = HashMap::from([
+        ("auth_player_name", "AMagicPear".to_string()),
+        ("natives_directory", "/tmp/natives".to_string()),
+    ]);
+    assert_eq!(
+        substitute_placeholders("${auth_player_name}", &placeholders),
+        "AMagicPear"
+    );
+    assert_eq!(
+        substitute_placeholders("-Djava.library.path=${natives_directory}", &placeholders),
+        "-Djava.library.path=/tmp/natives"
+    );
+    assert_eq!(
+        substitute_placeholders("${unknown} ${auth_player_name", &placeholders),
+        "${unknown} ${auth_player_name"
+    );
+}
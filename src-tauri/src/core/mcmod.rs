@@ -14,3 +14,32 @@ impl Default for PluginType {
         PluginType::Vanilla
     }
 }
+
+impl PluginType {
+    /// guess the mod loader from the libraries of a version json, before merging with its parent
+    pub fn detect(version_json: &serde_json::Value) -> Self {
+        let libraries = version_json["libraries"].as_array();
+        let has_library = |prefix: &str| {
+            libraries.is_some_and(|libraries| {
+                libraries.iter().any(|library| {
+                    library["name"]
+                        .as_str()
+                        .is_some_and(|name| name.starts_with(prefix))
+                })
+            })
+        };
+        if has_library("net.neoforged") {
+            PluginType::NeoForge
+        } else if has_library("net.minecraftforge:") {
+            PluginType::Forge
+        } else if has_library("net.fabricmc:fabric-loader") {
+            PluginType::Fabric
+        } else if has_library("org.quiltmc:quilt-loader") {
+            PluginType::Quilt
+        } else if has_library("optifine:") {
+            PluginType::Optifine
+        } else {
+            PluginType::Vanilla
+        }
+    }
+}
@@ -1,9 +1,17 @@
 pub(crate) mod api_client;
 pub(crate) mod auth;
+pub(crate) mod authlib_injector;
+pub(crate) mod crash;
+pub(crate) mod download_limit;
+pub(crate) mod download_queue;
 pub(crate) mod downloader;
 pub(crate) mod game;
+pub(crate) mod instance_settings;
 pub(crate) mod java;
+pub(crate) mod launch_script;
 pub(crate) mod launcher;
 pub(crate) mod mcmod;
 pub(crate) mod platform;
+pub(crate) mod process;
 pub(crate) mod repository;
+pub(crate) mod task_manager;
@@ -0,0 +1,252 @@
+//! Supervision of running game processes.
+//! Output of the game is captured line by line and streamed to the frontend,
+//! the exit status and play duration are recorded once the game exits.
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::Child,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use dashmap::DashMap;
+use tauri::ipc::Channel;
+
+use crate::core::{
+    crash::{self, CrashAnalysis},
+    game::GameInstance,
+};
+
+/// lines of output kept for each game, older lines are dropped
+const MAX_OUTPUT_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// events of a game process sent to the frontend
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameLogEvent {
+    Output {
+        pid: u32,
+        stream: LogStream,
+        line: String,
+    },
+    Exited {
+        pid: u32,
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        crash: Option<CrashAnalysis>,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RunningGameInfo {
+    pub pid: u32,
+    pub instance_id: String,
+    pub instance_name: String,
+    /// the directory the game runs in, which is the version folder if isolated
+    pub game_dir: PathBuf,
+    /// unix timestamp in milliseconds
+    pub started_at: i64,
+}
+
+/// record of a game which has exited
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GameExit {
+    pub info: RunningGameInfo,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// the last lines of output before exiting
+    pub output: Vec<String>,
+    /// analysis of the crash if the game exited abnormally
+    pub crash: Option<CrashAnalysis>,
+}
+
+struct RunningGame {
+    info: RunningGameInfo,
+    child: Arc<Mutex<Child>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GameProcessError {
+    #[error("no running game with pid {0}")]
+    NotRunning(u32),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// registry of the games launched by this launcher
+#[derive(Default)]
+pub struct GameRegistry {
+    running: DashMap<u32, RunningGame>,
+    exited: DashMap<u32, GameExit>,
+}
+
+impl GameRegistry {
+    /// take over a spawned game process, its stdout and stderr should be piped
+    pub fn supervise(
+        self: &Arc<Self>,
+        mut child: Child,
+        instance: &GameInstance,
+        game_dir: &Path,
+        on_log: Channel<GameLogEvent>,
+    ) -> u32 {
+        let pid = child.id();
+        let started = Instant::now();
+        let started_time = SystemTime::now();
+        let info = RunningGameInfo {
+            pid,
+            instance_id: instance.id.clone(),
+            instance_name: instance.name.clone(),
+            game_dir: game_dir.to_path_buf(),
+            started_at: chrono::Local::now().timestamp_millis(),
+        };
+        let output = Arc::new(Mutex::new(VecDeque::new()));
+        let mut readers = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(spawn_reader(
+                pid,
+                stdout,
+                LogStream::Stdout,
+                output.clone(),
+                on_log.clone(),
+            ));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(spawn_reader(
+                pid,
+                stderr,
+                LogStream::Stderr,
+                output.clone(),
+                on_log.clone(),
+            ));
+        }
+        let child = Arc::new(Mutex::new(child));
+        self.running.insert(
+            pid,
+            RunningGame {
+                info: info.clone(),
+                child: child.clone(),
+            },
+        );
+        log::info!("supervising game {} with pid {}", info.instance_id, pid);
+
+        let registry = Arc::clone(self);
+        std::thread::spawn(move || {
+            // poll instead of wait, so the child is not locked and could be killed
+            let exit_code = loop {
+                match child.lock().unwrap().try_wait() {
+                    Ok(Some(status)) => break status.code(),
+                    Ok(None) => {}
+                    Err(e) => {
+                        log::error!("failed to wait game {}: {}", pid, e);
+                        break None;
+                    }
+                }
+                std::thread::sleep(Duration::from_millis(200));
+            };
+            // drain the rest of output
+            for reader in readers {
+                let _ = reader.join();
+            }
+            let duration_ms = started.elapsed().as_millis() as u64;
+            log::info!(
+                "game {} exited with code {:?} after {}ms",
+                pid,
+                exit_code,
+                duration_ms
+            );
+            registry.running.remove(&pid);
+            let output: Vec<String> = std::mem::take(&mut *output.lock().unwrap()).into();
+            // killed by the user or a signal if there is no exit code
+            let crash = exit_code.is_some_and(|code| code != 0).then(|| {
+                let analysis = crash::analyze(&output, &info.game_dir, Some(started_time));
+                log::info!("crash analysis of game {}: {:?}", pid, analysis.causes);
+                analysis
+            });
+            registry.exited.insert(
+                pid,
+                GameExit {
+                    info,
+                    exit_code,
+                    duration_ms,
+                    output,
+                    crash: crash.clone(),
+                },
+            );
+            let _ = on_log.send(GameLogEvent::Exited {
+                pid,
+                exit_code,
+                duration_ms,
+                crash,
+            });
+        });
+        pid
+    }
+
+    pub fn running_games(&self) -> Vec<RunningGameInfo> {
+        self.running
+            .iter()
+            .map(|entry| entry.value().info.clone())
+            .collect()
+    }
+
+    /// the exit record of a game, if it has exited
+    pub fn exit_record(&self, pid: u32) -> Option<GameExit> {
+        self.exited.get(&pid).map(|entry| entry.value().clone())
+    }
+
+    pub fn kill(&self, pid: u32) -> Result<(), GameProcessError> {
+        let child = self
+            .running
+            .get(&pid)
+            .map(|entry| entry.value().child.clone())
+            .ok_or(GameProcessError::NotRunning(pid))?;
+        log::info!("killing game with pid {}", pid);
+        child.lock().unwrap().kill()?;
+        Ok(())
+    }
+}
+
+/// read the stream line by line on a thread, until the process closes it
+fn spawn_reader(
+    pid: u32,
+    stream: impl Read + Send + 'static,
+    kind: LogStream,
+    output: Arc<Mutex<VecDeque<String>>>,
+    on_log: Channel<GameLogEvent>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut reader = BufReader::new(stream);
+        let mut buffer = Vec::new();
+        // the game may not output utf-8, so read bytes rather than lines
+        while let Ok(n) = reader.read_until(b'\n', &mut buffer) {
+            if n == 0 {
+                break;
+            }
+            let line = String::from_utf8_lossy(&buffer)
+                .trim_end_matches(['\r', '\n'])
+                .to_string();
+            buffer.clear();
+            {
+                let mut output = output.lock().unwrap();
+                if output.len() >= MAX_OUTPUT_LINES {
+                    output.pop_front();
+                }
+                output.push_back(line.clone());
+            }
+            let _ = on_log.send(GameLogEvent::Output {
+                pid,
+                stream: kind,
+                line,
+            });
+        }
+    })
+}
@@ -0,0 +1,182 @@
+//! The download tasks running by the task id the frontend gives, which can be paused,
+//! resumed and cancelled. A paused download stops at the chunk it is receiving and keeps
+//! its part file, so it is resumed with a range request.
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TaskManagerError {
+    #[error("task {0} is not running")]
+    NotRunning(i32),
+}
+
+struct TaskHandle {
+    cancel: CancellationToken,
+    paused: watch::Sender<bool>,
+}
+
+/// what the downloads of a task listen to
+#[derive(Clone)]
+pub struct TaskSignal {
+    cancel: CancellationToken,
+    paused: watch::Receiver<bool>,
+}
+
+impl TaskSignal {
+    /// a signal never paused nor cancelled, for downloads not owned by a task
+    pub fn none() -> Self {
+        let (_, paused) = watch::channel(false);
+        Self {
+            cancel: CancellationToken::new(),
+            paused,
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// wait while the task is paused, returns false if it is cancelled
+    pub async fn proceed(&mut self) -> bool {
+        loop {
+            if self.cancel.is_cancelled() {
+                return false;
+            }
+            if !*self.paused.borrow_and_update() {
+                return true;
+            }
+            tokio::select! {
+                _ = self.cancel.cancelled() => return false,
+                changed = self.paused.changed() => {
+                    // the task is gone, nothing can resume it
+                    if changed.is_err() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// resolves once the task is paused or cancelled
+    pub async fn interrupted(&mut self) {
+        loop {
+            if *self.paused.borrow_and_update() {
+                return;
+            }
+            tokio::select! {
+                _ = self.cancel.cancelled() => return,
+                changed = self.paused.changed() => {
+                    if changed.is_err() {
+                        self.cancel.cancelled().await;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// the tasks registered, a task is removed when it is dropped
+#[derive(Default)]
+pub struct TaskManager {
+    tasks: DashMap<i32, TaskHandle>,
+}
+
+impl TaskManager {
+    /// register a task to be controlled by its id until the returned task is dropped
+    pub fn register(self: &Arc<Self>, task_id: i32) -> RunningTask {
+        let cancel = CancellationToken::new();
+        let (paused, paused_rx) = watch::channel(false);
+        let handle = TaskHandle {
+            cancel: cancel.clone(),
+            paused,
+        };
+        if let Some(old) = self.tasks.insert(task_id, handle) {
+            log::warn!(
+                "task {} registered twice, the old one is cancelled",
+                task_id
+            );
+            old.cancel.cancel();
+        }
+        RunningTask {
+            manager: Arc::clone(self),
+            task_id,
+            signal: TaskSignal {
+                cancel,
+                paused: paused_rx,
+            },
+        }
+    }
+
+    pub fn pause(&self, task_id: i32) -> Result<(), TaskManagerError> {
+        self.handle(task_id, |handle| {
+            handle.paused.send_replace(true);
+        })
+    }
+
+    pub fn resume(&self, task_id: i32) -> Result<(), TaskManagerError> {
+        self.handle(task_id, |handle| {
+            handle.paused.send_replace(false);
+        })
+    }
+
+    /// the downloads stop and remove their part files, a paused task is cancelled as well
+    pub fn cancel(&self, task_id: i32) -> Result<(), TaskManagerError> {
+        self.handle(task_id, |handle| handle.cancel.cancel())
+    }
+
+    fn handle(&self, task_id: i32, f: impl FnOnce(&TaskHandle)) -> Result<(), TaskManagerError> {
+        let handle = self
+            .tasks
+            .get(&task_id)
+            .ok_or(TaskManagerError::NotRunning(task_id))?;
+        f(&handle);
+        Ok(())
+    }
+}
+
+/// a task registered in the manager
+pub struct RunningTask {
+    manager: Arc<TaskManager>,
+    task_id: i32,
+    pub signal: TaskSignal,
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        // the id may have been taken by another task since
+        self.manager.tasks.remove_if(&self.task_id, |_, handle| {
+            handle.paused.subscribe().same_channel(&self.signal.paused)
+        });
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn task_manager_test() {
+    let manager = Arc::new(TaskManager::default());
+    let task = manager.register(1);
+    let mut signal = task.signal.clone();
+    assert!(signal.proceed().await);
+
+    manager.pause(1).unwrap();
+    signal.interrupted().await;
+    let waiting = tokio::spawn({
+        let mut signal = signal.clone();
+        async move { signal.proceed().await }
+    });
+    manager.resume(1).unwrap();
+    assert!(waiting.await.unwrap());
+
+    manager.cancel(1).unwrap();
+    assert!(!signal.proceed().await);
+    drop(task);
+    assert!(matches!(
+        manager.pause(1),
+        Err(TaskManagerError::NotRunning(1))
+    ));
+    assert!(TaskSignal::none().proceed().await);
+}
@@ -60,6 +60,13 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::launch_game,
+            commands::export_launch_script,
+            commands::get_running_games,
+            commands::get_game_exit,
+            commands::kill_game,
+            commands::pause_task,
+            commands::resume_task,
+            commands::cancel_task,
             util::toys::get_lucky_today,
             commands::add_java,
             commands::get_java_list,
@@ -67,13 +74,30 @@ pub fn run() {
             commands::get_repositories,
             commands::add_new_repository,
             commands::get_account,
+            commands::microsoft_login,
+            commands::yggdrasil_login,
+            commands::yggdrasil_select_profile,
+            commands::yggdrasil_signout,
+            commands::get_accounts,
+            commands::add_account,
+            commands::add_offline_account,
+            commands::remove_account,
+            commands::move_account,
+            commands::switch_account,
             commands::get_instances_in_repository,
             commands::select_instance,
             commands::get_version_manifest,
             commands::handle_clicked_on_version,
             downloader::minecraft_resource::download_minecraft_version,
+            downloader::minecraft_resource::resume_queued_download,
+            downloader::minecraft_resource::get_queued_downloads,
+            downloader::minecraft_resource::discard_queued_download,
             commands::get_plugin_versions,
             commands::get_active_instance,
+            commands::get_instance_settings,
+            commands::set_instance_settings,
+            commands::get_instance_mods,
+            commands::get_instance_saves,
             util::server_query::server_query,
             util::skin::fetch_username_uuid,
             util::skin::fetch_uuid_profile,
@@ -3,10 +3,18 @@ use tokio::sync::Mutex;
 use crate::{
     core::{
         api_client::{ApiProvider, MinecraftApiClient},
-        auth::Account,
-        game::GameInstance,
-        java::JavaRuntime,
+        auth::{
+            Account,
+            credentials::{self, CredentialStore},
+            microsoft::MicrosoftAuthConfig,
+        },
+        download_limit::DownloadSettings,
+        download_queue::DownloadQueue,
+        game::{GameInstance, VersionIsolation},
+        java::{JavaKindPolicy, JavaRuntime},
+        process::GameRegistry,
         repository::GameRepository,
+        task_manager::TaskManager,
     },
     setup::constants::USER_AGENT,
     util,
@@ -33,6 +41,14 @@ pub struct PCLSetupInfo {
     pub api_provider: ApiProvider,
     pub max_memory: usize,
     pub default_java: Option<Arc<JavaRuntime>>,
+    #[serde(default)]
+    pub version_isolation: VersionIsolation,
+    #[serde(default)]
+    pub java_kind_policy: JavaKindPolicy,
+    #[serde(default)]
+    pub microsoft_auth: MicrosoftAuthConfig,
+    #[serde(default)]
+    pub download: DownloadSettings,
 }
 
 /// PCL theme
@@ -52,6 +68,10 @@ impl Default for PCLSetupInfo {
             api_provider: ApiProvider::Official,
             max_memory: 2048,
             default_java: None,
+            version_isolation: VersionIsolation::default(),
+            java_kind_policy: JavaKindPolicy::default(),
+            microsoft_auth: MicrosoftAuthConfig::default(),
+            download: DownloadSettings::default(),
         }
     }
 }
@@ -60,12 +80,19 @@ impl Default for PCLSetupInfo {
 #[derive(serde::Serialize, serde::Deserialize, Default)]
 pub struct AppState {
     pub java_runtimes: Vec<crate::core::java::JavaRuntime>,
+    /// the tokens are saved in the credential store instead
+    #[serde(serialize_with = "credentials::serialize_accounts")]
     pub accounts: Vec<Account>,
     pub pcl_setup_info: crate::setup::PCLSetupInfo,
+    #[serde(serialize_with = "credentials::serialize_active_account")]
     pub active_account: Option<Arc<Account>>,
     pub repositories: Vec<GameRepository>,
     pub active_game_instance: Option<Arc<GameInstance>>,
     pub active_repo_path: PathBuf,
+    #[serde(skip)]
+    pub game_registry: Arc<GameRegistry>,
+    #[serde(skip)]
+    pub task_manager: Arc<TaskManager>,
 }
 
 /// config manager, for loading and saving config file
@@ -78,6 +105,9 @@ pub struct ConfigManager {
     pub app_state: Arc<Mutex<AppState>>,
     pub api_client: MinecraftApiClient,
     pub pcl_identifier: String,
+    credential_store: CredentialStore,
+    /// the download tasks to resume after a restart
+    pub download_queue: DownloadQueue,
 }
 
 #[derive(Debug)]
@@ -87,6 +117,7 @@ pub enum ConfigManagerError {
     ConfigFileCorrupted,
     IdentifierFailure,
     ApiClientBuildError,
+    CredentialsFailure,
 }
 
 pub static CONFIG_MANAGER: LazyLock<Option<ConfigManager>> = LazyLock::new(|| {
@@ -121,6 +152,9 @@ impl ConfigManager {
                 .map_err(|_| ConfigManagerError::IdentifierFailure)?;
             pcl_identifier
         };
+        let machine_secret = util::crypto::get_machine_secret()
+            .inspect_err(|err| log::error!("no machine secret for the credentials: {}", err))
+            .ok();
         let instance = Self {
             config_path,
             config_dir: config_dir.to_path_buf(),
@@ -132,15 +166,27 @@ impl ConfigManager {
                     .map_err(|_| ConfigManagerError::ApiClientBuildError)?,
                 &ApiProvider::default(),
             ),
+            credential_store: CredentialStore::new(config_dir, machine_secret.as_deref()),
+            download_queue: DownloadQueue::load(config_dir),
             pcl_identifier,
         };
-        if !instance.config_path.exists()
-            || !instance.config_path.is_file()
-            || instance.load().is_err()
-        {
+        if !instance.config_path.exists() || !instance.config_path.is_file() {
             log::warn!("Unabled to load config file, try to init");
             instance.init()?;
             instance.save()?;
+        } else {
+            match instance.load() {
+                Ok(true) => {
+                    log::info!("moving the tokens in config file to the credential store");
+                    instance.save()?;
+                }
+                Ok(false) => {}
+                Err(_) => {
+                    log::warn!("Unabled to load config file, try to init");
+                    instance.init()?;
+                    instance.save()?;
+                }
+            }
         }
         log::debug!("launched config at {:?}", instance.config_path);
         Ok(instance)
@@ -167,18 +213,14 @@ impl ConfigManager {
         //     "HMCL",
         //     PathBuf::from("/Users/amagicpear/HMCL/.minecraft"),
         // ));
-        state.active_account = Some(Arc::new(Account::Offline {
-            username: "AMagicPear".to_string(),
-            uuid: "31bbe5379fea4e68aa4ad7aacca23d13".to_string(),
-        }));
         // state.pcl_setup_info.default_java =
         //     Some(Arc::new(JavaRuntime::try_from("/usr/bin/java").unwrap()));
         Ok(())
     }
 
-    /// load the config file
+    /// load the config file, returns whether tokens are found in it in plain text
     /// TODO: Partial config support
-    fn load(&self) -> Result<(), ConfigManagerError> {
+    fn load(&self) -> Result<bool, ConfigManagerError> {
         let file = std::fs::File::open(&self.config_path)
             .map_err(|_| ConfigManagerError::ConfigFileNotFound)?;
         let mut reader = std::io::BufReader::new(file);
@@ -186,18 +228,34 @@ impl ConfigManager {
             .map_err(|_| ConfigManagerError::ConfigFileCorrupted)?;
         let mut state = self.app_state.blocking_lock();
         *state = state_read;
+        let state = &mut *state;
+        // accounts whose tokens can't be restored have to log in again
+        let plaintext = self
+            .credential_store
+            .restore(&mut state.accounts, &mut state.active_account)
+            .unwrap_or_else(|err| {
+                log::warn!("failed to restore credentials: {}", err);
+                false
+            });
         // update the api provider
         self.api_client
             .switch_provider(&state.pcl_setup_info.api_provider);
-        Ok(())
+        Ok(plaintext)
     }
 
     /// save the config file
     pub fn save(&self) -> Result<(), ConfigManagerError> {
+        let state = self.app_state.blocking_lock();
+        // the tokens are saved first so they are never lost from both files
+        self.credential_store
+            .save(state.accounts.iter().chain(state.active_account.as_deref()))
+            .map_err(|err| {
+                log::error!("failed to save credentials: {}", err);
+                ConfigManagerError::CredentialsFailure
+            })?;
         let file = std::fs::File::create(&self.config_path)
             .map_err(|_| ConfigManagerError::ConfigFileNotFound)?;
         let mut writer = std::io::BufWriter::new(file);
-        let state = self.app_state.blocking_lock();
         serde_json::to_writer_pretty(&mut writer, &*state)
             .map_err(|_| ConfigManagerError::ConfigFileCorrupted)?;
         writer
@@ -210,6 +268,11 @@ impl ConfigManager {
     pub fn identifier_path(&self) -> PathBuf {
         self.config_dir.join("pcl_identifier.txt")
     }
+
+    #[inline]
+    pub fn authlib_injector_dir(&self) -> PathBuf {
+        self.config_dir.join("authlib-injector")
+    }
 }
 
 #[test]
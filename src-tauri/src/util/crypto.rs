@@ -1,5 +1,13 @@
 use std::process::Command;
 
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng},
+};
+use sha2::{Digest, Sha256};
+
+const NONCE_SIZE: usize = 12;
+
 #[cfg(target_os = "macos")]
 pub fn get_board_serial() -> anyhow::Result<String> {
     let output = Command::new("system_profiler")
@@ -80,6 +88,54 @@ pub fn get_pcl_hash(str: &str) -> u64 {
     result ^ 0xA98F501BC684032F as u64
 }
 
+/// an identifier bound to this machine which the launcher never writes down.
+/// the board serial, or the machine id on linux where the serial may be readable only by root,
+/// the machine id is readable by every local user
+pub fn get_machine_secret() -> anyhow::Result<String> {
+    get_board_serial().or_else(|err| {
+        std::fs::read_to_string("/etc/machine-id")
+            .ok()
+            .filter(|machine_id| cfg!(target_os = "linux") && !machine_id.trim().is_empty())
+            .ok_or(err)
+    })
+}
+
+/// the key of `encrypt`, bound to this machine through its secret
+pub fn derive_key(machine_id: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"PCL.Proto credentials\0");
+    hasher.update(machine_id.trim().as_bytes());
+    hasher.finalize().into()
+}
+
+/// aes-256-gcm, the random nonce is put before the ciphertext
+pub fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|err| anyhow::anyhow!("encryption failed: {}", err))?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+pub fn decrypt(key: &[u8; 32], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if data.len() < NONCE_SIZE {
+        return Err(anyhow::anyhow!("encrypted data too short"));
+    }
+    let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|err| anyhow::anyhow!("decryption failed: {}", err))
+}
+
+#[test]
+fn encrypt_test() {
+    let key = derive_key("a98f501bc684032f");
+    let data = encrypt(&key, b"token").unwrap();
+    assert_eq!(decrypt(&key, &data).unwrap(), b"token");
+    assert!(decrypt(&derive_key("another machine"), &data).is_err());
+}
+
 #[test]
 fn serial_test() {
     let serial = get_board_serial().unwrap();
@@ -20,8 +20,12 @@ pub fn find_file_of_name(
     None
 }
 
+/// an empty sha1 is unknown, then any file existing matches
 pub fn check_sha1(file: &Path, given: &str) -> Result<bool, std::io::Error> {
     let file = std::fs::File::open(file)?;
+    if given.is_empty() {
+        return Ok(true);
+    }
     let mut reader = BufReader::new(file);
     let mut hasher = sha1::Sha1::new();
     let mut buffer = [0u8; 8192];
@@ -37,6 +41,34 @@ pub fn check_sha1(file: &Path, given: &str) -> Result<bool, std::io::Error> {
     Ok(computed == given)
 }
 
+/// extract all the files in a zip archive into the destination,
+/// entries starting with any of the exclude prefixes are skipped
+pub fn extract_zip(archive: &Path, dest: &Path, exclude: &[String]) -> zip::result::ZipResult<()> {
+    let file = std::fs::File::open(archive)?;
+    let mut zip = zip::ZipArchive::new(BufReader::new(file))?;
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        if entry.is_dir()
+            || exclude
+                .iter()
+                .any(|prefix| entry.name().starts_with(prefix))
+        {
+            continue;
+        }
+        // skip the entries trying to escape the destination
+        let Some(relative_path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        let out_path = dest.join(relative_path);
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut out_file = std::fs::File::create(&out_path)?;
+        std::io::copy(&mut entry, &mut out_file)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 #[test]
 fn check_sha1_test() {
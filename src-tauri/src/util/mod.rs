@@ -1,5 +1,7 @@
 pub mod crypto;
 pub mod file;
+#[cfg(test)]
+pub mod mock_server;
 pub mod scaffolding;
 pub mod server_query;
 pub mod skin;
@@ -1,5 +1,5 @@
 // import getSkinUrl from "@/util/skinGetter";
-import { invoke } from '@tauri-apps/api/core'
+import { Channel, invoke } from '@tauri-apps/api/core'
 import { defineStore } from 'pinia'
 import { ref } from 'vue'
 import SteveSkin from '/default-skin/Steve_(classic_texture)_JE6.png'
@@ -8,17 +8,47 @@ interface AccountInner {
   username: string
   uuid: string
 }
-interface Account {
+export interface Account {
   Offline?: AccountInner
+  Microsoft?: AccountInner
+  Yggdrasil?: AccountInner
+  AuthlibInjector?: AccountInner & { server_name: string; server_homepage: string }
 }
 
+// 微软登录的设备代码，需要用户打开 verification_uri 并输入 user_code
+export interface DeviceCode {
+  user_code: string
+  verification_uri: string
+  expires_in: number
+  message: string
+}
+
+export interface GameProfile {
+  id: string
+  name: string
+}
+
+// 外置登录的结果，账号有多个角色且未选择时需要调用 yggdrasilSelectProfile
+export type YggdrasilLoginResult =
+  | { status: 'logged_in'; account: Account }
+  | {
+      status: 'select_profile'
+      server: unknown
+      access_token: string
+      client_token: string
+      profiles: GameProfile[]
+    }
+
 export const useAccountInfo = defineStore('account-info', () => {
   const username = ref<string>()
   const uuid = ref<string>()
 
   async function initialize() {
     try {
-      const account = await invoke<Account>('get_account')
+      const account = await invoke<Account | null>('get_account')
+      // 没有账号时清空显示
+      username.value = undefined
+      uuid.value = undefined
       for (const key in account) {
         if (Object.prototype.hasOwnProperty.call(account, key)) {
           const element = (account as any)[key] as AccountInner
@@ -32,9 +62,81 @@ export const useAccountInfo = defineStore('account-info', () => {
     }
   }
 
+  async function microsoftLogin(onDeviceCode: (code: DeviceCode) => void) {
+    const channel = new Channel<DeviceCode>()
+    channel.onmessage = onDeviceCode
+    await invoke<Account>('microsoft_login', { on_device_code: channel })
+    await initialize()
+  }
+
+  // serverUrl 为外置登录服务器的网址或 API 地址，不填则为 Mojang
+  async function yggdrasilLogin(username: string, password: string, serverUrl?: string) {
+    const result = await invoke<YggdrasilLoginResult>('yggdrasil_login', {
+      server_url: serverUrl,
+      username,
+      password,
+    })
+    if (result.status === 'logged_in') {
+      await initialize()
+    }
+    return result
+  }
+
+  async function yggdrasilSelectProfile(
+    pending: Extract<YggdrasilLoginResult, { status: 'select_profile' }>,
+    profileId: string,
+  ) {
+    const { status: _status, ...rest } = pending
+    await invoke<Account>('yggdrasil_select_profile', { pending: rest, profile_id: profileId })
+    await initialize()
+  }
+
+  // 所有已登录的账号，顺序即为显示顺序
+  const accounts = ref<Account[]>([])
+
+  async function fetchAccounts() {
+    accounts.value = await invoke<Account[]>('get_accounts')
+  }
+
+  async function addAccount(account: Account) {
+    await invoke('add_account', { account })
+    await Promise.all([initialize(), fetchAccounts()])
+  }
+
+  // 离线账号的 UUID 默认由用户名生成，与原版服务器一致；迁移存档时可指定
+  async function addOfflineAccount(name: string, customUuid?: string) {
+    await invoke<Account>('add_offline_account', { username: name, uuid: customUuid })
+    await Promise.all([initialize(), fetchAccounts()])
+  }
+
+  async function removeAccount(index: number) {
+    await invoke('remove_account', { account_index: index })
+    await Promise.all([initialize(), fetchAccounts()])
+  }
+
+  async function moveAccount(from: number, to: number) {
+    await invoke('move_account', { from, to })
+    await fetchAccounts()
+  }
+
+  async function switchAccount(index: number) {
+    await invoke<Account>('switch_account', { account_index: index })
+    await initialize()
+  }
+
   return {
     username,
     uuid,
     initialize,
+    microsoftLogin,
+    yggdrasilLogin,
+    yggdrasilSelectProfile,
+    accounts,
+    fetchAccounts,
+    addAccount,
+    addOfflineAccount,
+    removeAccount,
+    moveAccount,
+    switchAccount,
   }
 })
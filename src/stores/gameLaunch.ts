@@ -1,4 +1,5 @@
 import type GameInstance from '@/types/gameInstance'
+import type { InstanceSettings } from '@/types/gameInstance'
 import type { pluginType } from '../types/gameInfo'
 import { defineStore } from 'pinia'
 import { computed, ref } from 'vue'
@@ -6,6 +7,7 @@ import { invoke } from '@tauri-apps/api/core'
 
 export const useSelectedInstance = defineStore('selected-instance', () => {
   const instance_info = ref<GameInstance | null>(null)
+  const settings = ref<InstanceSettings | null>(null)
 
   const plugins = computed<pluginType[]>(
     () => Object.keys(instance_info.value?.pluginsVersion || {}) as pluginType[],
@@ -15,9 +17,32 @@ export const useSelectedInstance = defineStore('selected-instance', () => {
     instance_info.value = await invoke<GameInstance | null>('get_active_instance')
   }
 
+  async function fetchSettings() {
+    settings.value = await invoke<InstanceSettings>('get_instance_settings')
+  }
+
+  async function saveSettings(newSettings: InstanceSettings) {
+    await invoke('set_instance_settings', { settings: newSettings })
+    settings.value = newSettings
+  }
+
+  // 版本隔离决定了 mods 与存档所在的文件夹
+  async function fetchMods() {
+    return await invoke<string[]>('get_instance_mods')
+  }
+
+  async function fetchSaves() {
+    return await invoke<string[]>('get_instance_saves')
+  }
+
   return {
     instance_info,
+    settings,
     plugins,
     fetch,
+    fetchSettings,
+    saveSettings,
+    fetchMods,
+    fetchSaves,
   }
 })
@@ -0,0 +1,78 @@
+import { Channel, invoke } from '@tauri-apps/api/core'
+import { defineStore } from 'pinia'
+import { ref } from 'vue'
+
+const MAX_LOG_LINES = 2000
+
+export interface CrashCauseReport {
+  cause: { kind: string; [field: string]: unknown }
+  evidence: string
+  suggestion: string
+}
+
+export interface CrashAnalysis {
+  causes: CrashCauseReport[]
+  crash_report: string | null
+  jvm_error_log: string | null
+  latest_log: string | null
+}
+
+export type GameLogEvent =
+  | { type: 'output'; pid: number; stream: 'stdout' | 'stderr'; line: string }
+  | {
+      type: 'exited'
+      pid: number
+      exit_code: number | null
+      duration_ms: number
+      crash: CrashAnalysis | null
+    }
+
+export interface RunningGameInfo {
+  pid: number
+  instance_id: string
+  instance_name: string
+  game_dir: string
+  started_at: number
+}
+
+export const useGameProcess = defineStore('game-process', () => {
+  const logs = ref<Record<number, string[]>>({})
+  const runningGames = ref<RunningGameInfo[]>([])
+  const crashes = ref<Record<number, CrashAnalysis>>({})
+
+  function createLogChannel() {
+    const onLog = new Channel<GameLogEvent>()
+    onLog.onmessage = (event) => {
+      if (event.type === 'output') {
+        const lines = (logs.value[event.pid] ??= [])
+        lines.push(event.line)
+        if (lines.length > MAX_LOG_LINES) {
+          lines.shift()
+        }
+      } else {
+        runningGames.value = runningGames.value.filter((game) => game.pid !== event.pid)
+        if (event.crash) {
+          crashes.value[event.pid] = event.crash
+        }
+      }
+    }
+    return onLog
+  }
+
+  async function fetchRunningGames() {
+    runningGames.value = await invoke<RunningGameInfo[]>('get_running_games')
+  }
+
+  async function killGame(pid: number) {
+    await invoke('kill_game', { pid })
+  }
+
+  return {
+    logs,
+    runningGames,
+    crashes,
+    createLogChannel,
+    fetchRunningGames,
+    killGame,
+  }
+})
@@ -1,6 +1,7 @@
 import { FloatButtonType, useFloatButton } from '@/composables/useFloatButton'
 import { Channel, invoke } from '@tauri-apps/api/core'
 import { defineStore } from 'pinia'
+import { useGameProcess } from './gameProcess'
 import { computed, ref } from 'vue'
 
 export enum TaskStatus {
@@ -8,6 +9,8 @@ export enum TaskStatus {
   Running = 1,
   Completed = 2,
   Failed = 3,
+  Paused = 4,
+  Cancelled = 5,
 }
 
 export class Task {
@@ -70,6 +73,12 @@ class TaskItem implements ITaskItem {
   }
 }
 
+// launch_game 失败的原因，relogin_required 表示账号需要重新登录
+export interface LaunchGameError {
+  kind: 'relogin_required' | 'failed'
+  message: string
+}
+
 interface TaskItemReport {
   item_id: number
   task_id: number
@@ -79,8 +88,21 @@ interface TaskItemReport {
   speed: number | null
 }
 
+// 上次关闭启动器时未完成的下载，记录在配置目录中
+export interface QueuedDownload {
+  id: number
+  job: {
+    kind: 'minecraft_version'
+    version_id: string
+    instance_name: string
+    repo_path: string
+  }
+  queued_at: number
+}
+
 export const useTaskManager = defineStore('task-manager', () => {
   const tasks = ref<Task[]>([])
+  const queuedDownloads = ref<QueuedDownload[]>([])
   let current_taskid = -1
   const totalProgress = computed(() => {
     return (
@@ -102,13 +124,13 @@ export const useTaskManager = defineStore('task-manager', () => {
     }, 0)
   })
   const activeTaskCount = computed(() => {
-    return tasks.value.filter((task) => task.status !== TaskStatus.Completed).length
+    return tasks.value.filter(
+      (task) => task.status !== TaskStatus.Completed && task.status !== TaskStatus.Cancelled,
+    ).length
   })
   const floatButton = useFloatButton()
 
-  async function startDownloadMCVersion(version_id: string, instance_name: string) {
-    let newTask = new Task(++current_taskid, version_id)
-    tasks.value.push(newTask)
+  function createTaskChannel() {
     const onEvent = new Channel<TaskItemReport>()
     onEvent.onmessage = (message) => {
       const taskToUpdate = tasks.value[message.task_id]
@@ -118,6 +140,8 @@ export const useTaskManager = defineStore('task-manager', () => {
           itemToUpdate.update(message)
           if (taskToUpdate.items.every((item) => item.status === TaskStatus.Completed)) {
             taskToUpdate.status = TaskStatus.Completed
+          } else if (message.status === TaskStatus.Cancelled) {
+            taskToUpdate.status = TaskStatus.Cancelled
           }
           taskToUpdate.progress =
             taskToUpdate.items.reduce((total, item) => total + item.progress, 0) /
@@ -129,12 +153,51 @@ export const useTaskManager = defineStore('task-manager', () => {
         }
       }
     }
-    await invoke('download_minecraft_version', {
-      on_event: onEvent,
-      version_id,
-      task_id: current_taskid,
-      instance_name,
-    })
+    return onEvent
+  }
+
+  async function startDownloadMCVersion(version_id: string, instance_name: string) {
+    await runDownloadTask(version_id, 'download_minecraft_version', { version_id, instance_name })
+  }
+
+  async function fetchQueuedDownloads() {
+    queuedDownloads.value = await invoke<QueuedDownload[]>('get_queued_downloads')
+  }
+
+  // 已校验过的文件会被跳过
+  async function resumeQueuedDownload(download: QueuedDownload) {
+    queuedDownloads.value = queuedDownloads.value.filter((item) => item.id !== download.id)
+    try {
+      await runDownloadTask(download.job.version_id, 'resume_queued_download', {
+        queue_id: download.id,
+      })
+    } finally {
+      await fetchQueuedDownloads()
+    }
+  }
+
+  // 同时删除下载了一半的实例文件夹
+  async function discardQueuedDownload(queue_id: number) {
+    await invoke('discard_queued_download', { queue_id })
+    queuedDownloads.value = queuedDownloads.value.filter((item) => item.id !== queue_id)
+  }
+
+  async function runDownloadTask(name: string, command: string, args: Record<string, unknown>) {
+    let newTask = new Task(++current_taskid, name)
+    tasks.value.push(newTask)
+    const onEvent = createTaskChannel()
+    try {
+      await invoke(command, {
+        ...args,
+        on_event: onEvent,
+        task_id: newTask.id,
+      })
+    } catch (err) {
+      // 用户取消的任务不算失败
+      if (findTask(newTask.id)?.status !== TaskStatus.Cancelled) {
+        throw err
+      }
+    }
     console.log('download task completed', tasks.value)
     if (
       activeTaskCount.value <= 0 ||
@@ -144,12 +207,60 @@ export const useTaskManager = defineStore('task-manager', () => {
     }
   }
 
+  // files missing or corrupted are repaired as a task before the game launches,
+  // rejects with a LaunchGameError
+  async function launchGameChecked(instance_name: string) {
+    let newTask = new Task(++current_taskid, instance_name)
+    tasks.value.push(newTask)
+    const onEvent = createTaskChannel()
+    const gameProcess = useGameProcess()
+    const pid = await invoke<number>('launch_game', {
+      on_event: onEvent,
+      on_log: gameProcess.createLogChannel(),
+      task_id: current_taskid,
+    })
+    await gameProcess.fetchRunningGames()
+    return pid
+  }
+
+  function findTask(task_id: number) {
+    return tasks.value.find((task) => task.id === task_id)
+  }
+
+  // 暂停后正在下载的文件保留进度，继续时从断点续传
+  async function pauseTask(task_id: number) {
+    await invoke('pause_task', { task_id })
+    const task = findTask(task_id)
+    if (task) task.status = TaskStatus.Paused
+  }
+
+  async function resumeTask(task_id: number) {
+    await invoke('resume_task', { task_id })
+    const task = findTask(task_id)
+    if (task) task.status = TaskStatus.Running
+  }
+
+  // 取消后未完成的文件会被删除
+  async function cancelTask(task_id: number) {
+    const task = findTask(task_id)
+    if (task) task.status = TaskStatus.Cancelled
+    await invoke('cancel_task', { task_id })
+  }
+
   return {
     tasks,
+    queuedDownloads,
     totalProgress,
     totalSpeed,
     totalRemaining,
     startDownloadMCVersion,
+    fetchQueuedDownloads,
+    resumeQueuedDownload,
+    discardQueuedDownload,
+    launchGameChecked,
+    pauseTask,
+    resumeTask,
+    cancelTask,
     activeTaskCount,
   }
 })
@@ -23,3 +23,18 @@ export interface GameRepository {
   name: string
   path: string
 }
+
+// 单个实例的启动设置，未设置的项使用全局设置
+export interface InstanceSettings {
+  min_memory: number | null
+  max_memory: number | null
+  extra_jvm_args: string[]
+  extra_game_args: string[]
+  window_width: number | null
+  window_height: number | null
+  fullscreen: boolean
+  java: 'Default' | { Custom: unknown }
+  wrapper_command: string | null
+  env: Record<string, string>
+  version_isolation: boolean | null
+}
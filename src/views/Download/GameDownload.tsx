@@ -1,12 +1,15 @@
 import CardInfoItem from '@/components/widget/CardInfoItem.vue'
+import PButton from '@/components/widget/PButton.vue'
 import PCard from '@/components/widget/PCard.vue'
 import { showIconPath, type showIconType } from '@/types/gameInfo'
 import { useMinecraftVersions } from '@/api/gameVersions'
-import { defineComponent } from 'vue'
+import { defineComponent, onMounted } from 'vue'
 import PLoading from '@/components/widget/PLoading.vue'
 import { useRouter } from 'vue-router'
 import { useI18n } from 'vue-i18n'
 import { info } from '@tauri-apps/plugin-log'
+import { useTaskManager, type QueuedDownload } from '@/stores/task'
+import { FloatButtonType, useFloatButton } from '@/composables/useFloatButton'
 
 export default defineComponent({
   emits: ['animateSubview'],
@@ -14,6 +17,11 @@ export default defineComponent({
     const router = useRouter()
     const { t } = useI18n()
     const versionDataRef = useMinecraftVersions()
+    const taskManager = useTaskManager()
+    const { floatButtonState, setFloatButton } = useFloatButton()
+
+    // 上次未完成或失败的下载
+    onMounted(() => taskManager.fetchQueuedDownloads())
 
     const onLoaded = () => {
       ctx.emit('animateSubview')
@@ -41,6 +49,37 @@ export default defineComponent({
         }}
       </PCard>
     )
+    const resumeDownload = (download: QueuedDownload) => {
+      setFloatButton(FloatButtonType.TaskManage)
+      floatButtonState.visible = true
+      taskManager.resumeQueuedDownload(download)
+    }
+
+    const renderQueuedDownloads = () =>
+      taskManager.queuedDownloads.length > 0 && (
+        <PCard title={`未完成的下载 (${taskManager.queuedDownloads.length})`}>
+          {taskManager.queuedDownloads.map((download) => (
+            <div key={download.id} style={{ display: 'flex', alignItems: 'center', gap: '8px' }}>
+              <CardInfoItem
+                style={{ flex: '1 1 auto' }}
+                icon={showIconPath.grass}
+                title={download.job.instance_name}
+                subtitle={`${download.job.version_id}, ${new Date(download.queued_at).toLocaleString()}`}
+                click={() => resumeDownload(download)}
+              />
+              <PButton inline type="tint" click={() => resumeDownload(download)}>继续</PButton>
+              <PButton
+                inline
+                type="warn"
+                click={() => taskManager.discardQueuedDownload(download.id)}
+              >
+                放弃
+              </PButton>
+            </div>
+          ))}
+        </PCard>
+      )
+
     function clickOnVersion(version: string, version_type: 'release' | 'snapshot' | 'old') {
       info(`clicked on version: ${version}`)
       router.push({
@@ -51,6 +90,8 @@ export default defineComponent({
     return () =>
       versionDataRef.value ? (
         <>
+          {renderQueuedDownloads()}
+
           <PCard title={t('download.latest')}>
             <CardInfoItem
               icon={showIconPath.grass}